@@ -107,7 +107,7 @@ impl PublicKey {
     pub fn from_base58_string<S: Into<String>>(val: S) -> Result<Self, SignatureError> {
         let bytes = bs58::decode(val.into())
             .into_vec()
-            .expect("TODO: deal with this failure case");
+            .map_err(|_| SignatureError::new())?;
         Self::from_bytes(&bytes)
     }
 
@@ -197,4 +197,15 @@ impl Signature {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
         Ok(Signature(ed25519_dalek::Signature::from_bytes(bytes)?))
     }
+
+    pub fn to_base58_string(&self) -> String {
+        bs58::encode(&self.to_bytes()).into_string()
+    }
+
+    pub fn from_base58_string<S: Into<String>>(val: S) -> Result<Self, SignatureError> {
+        let bytes = bs58::decode(val.into())
+            .into_vec()
+            .map_err(|_| SignatureError::new())?;
+        Self::from_bytes(&bytes)
+    }
 }
@@ -31,12 +31,28 @@ pub const NODE_IDENTITY_SIZE: usize = identity::PUBLIC_KEY_LENGTH;
 /// In this case it's an ipv6 socket address (with version prefix)
 pub const MAX_NODE_ADDRESS_UNPADDED_LEN: usize = 19;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum NymNodeRoutingAddressError {
     InsufficientNumberOfBytesAvailableError,
     InvalidIPVersion,
 }
 
+impl std::fmt::Display for NymNodeRoutingAddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NymNodeRoutingAddressError::InsufficientNumberOfBytesAvailableError => write!(
+                f,
+                "not enough bytes available to represent a node routing address"
+            ),
+            NymNodeRoutingAddressError::InvalidIPVersion => {
+                write!(f, "node routing address has an invalid ip version flag")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NymNodeRoutingAddressError {}
+
 /// Current representation of Node routing information used in Nym system.
 /// At this point of time it is a simple `SocketAddr`.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
@@ -244,4 +260,55 @@ mod tests {
         assert_eq!(address_v4, node_address1.try_into().unwrap());
         assert_eq!(address_v6, node_address2.try_into().unwrap());
     }
+
+    #[test]
+    fn nym_node_routing_address_can_be_converted_to_and_from_zero_padded_bytes_for_v6_address() {
+        let address = NymNodeRoutingAddress(SocketAddr::new(
+            IpAddr::from([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]),
+            1789,
+        ));
+        let address_bytes = address.as_zero_padded_bytes(NODE_ADDRESS_LENGTH);
+        assert_eq!(NODE_ADDRESS_LENGTH, address_bytes.len());
+        assert_eq!(
+            address,
+            NymNodeRoutingAddress::try_from_bytes(&address_bytes).unwrap()
+        )
+    }
+
+    #[test]
+    fn recovering_nym_node_routing_address_from_too_few_bytes_fails() {
+        assert_eq!(
+            NymNodeRoutingAddressError::InsufficientNumberOfBytesAvailableError,
+            NymNodeRoutingAddress::try_from_bytes(&[4, 0, 42, 1, 2, 3]).unwrap_err()
+        )
+    }
+
+    #[test]
+    fn recovering_nym_node_routing_address_from_truncated_v6_address_fails() {
+        let address = NymNodeRoutingAddress(SocketAddr::new(
+            IpAddr::from([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]),
+            42,
+        ));
+        let address_bytes = address.as_bytes();
+        assert_eq!(
+            NymNodeRoutingAddressError::InsufficientNumberOfBytesAvailableError,
+            NymNodeRoutingAddress::try_from_bytes(&address_bytes[..10]).unwrap_err()
+        )
+    }
+
+    #[test]
+    fn recovering_nym_node_routing_address_with_unknown_ip_version_fails() {
+        assert_eq!(
+            NymNodeRoutingAddressError::InvalidIPVersion,
+            NymNodeRoutingAddress::try_from_bytes(&[5, 0, 42, 1, 2, 3, 4]).unwrap_err()
+        )
+    }
+
+    #[test]
+    fn recovering_nym_node_routing_address_from_all_zero_bytes_fails() {
+        assert_eq!(
+            NymNodeRoutingAddressError::InvalidIPVersion,
+            NymNodeRoutingAddress::try_from_bytes(&[0; NODE_ADDRESS_LENGTH]).unwrap_err()
+        )
+    }
 }
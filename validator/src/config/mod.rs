@@ -13,8 +13,11 @@
 // limitations under the License.
 
 use crate::config::template::config_template;
+use crate::network::rest;
+use crate::services::mixmining;
 use config::NymConfig;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time;
 
@@ -23,12 +26,21 @@ mod template;
 // where applicable, the below are defined in milliseconds
 const DEFAULT_DIRECTORY_SERVER: &str = "https://directory.nymtech.net";
 
+// 'REST API'
+const DEFAULT_REST_API_PORT: u16 = 3000;
+// the password is read from the environment (or `.env`) so that it never ends up in config.toml
+const TLS_IDENTITY_PASSWORD_ENV: &str = "NYM_VALIDATOR_TLS_IDENTITY_PASSWORD";
+
 // 'MIXMINING'
 const DEFAULT_MIX_MINING_DELAY: u64 = 10_000;
 const DEFAULT_MIX_MINING_RESOLUTION_TIMEOUT: u64 = 5_000;
 const DEFAULT_MIX_MINING_CONNECTION_TIMEOUT: u64 = 1_500;
 
 const DEFAULT_NUMBER_OF_MIX_MINING_TEST_PACKETS: u64 = 2;
+// the defaults of the reputation settings are the ones of the mixmining service itself
+
+// 'TENDERMINT'
+const DEFAULT_ABCI_PORT: u16 = 26658;
 
 // 'DEBUG'
 const DEFAULT_PRESENCE_SENDING_DELAY: u64 = 3000;
@@ -38,6 +50,9 @@ const DEFAULT_PRESENCE_SENDING_DELAY: u64 = 3000;
 pub struct Config {
     validator: Validator,
 
+    #[serde(default)]
+    rest_api: RestApi,
+
     mix_mining: MixMining,
 
     tendermint: Tendermint,
@@ -93,6 +108,9 @@ impl Config {
         let id = id.into();
 
         // calls to any defaults requiring id (see: client, mixnode, provider):
+        if self.mix_mining.database_path.as_os_str().is_empty() {
+            self.mix_mining.database_path = self::MixMining::default_database_path(&id);
+        }
 
         self.validator.id = id;
         self
@@ -115,6 +133,87 @@ impl Config {
         self.config_directory().join(Self::config_file_name())
     }
 
+    pub fn get_rest_api_listening_address(&self) -> SocketAddr {
+        self.rest_api.listening_address
+    }
+
+    /// Path to the PKCS #12 identity used for serving the REST API over https,
+    /// if it's been configured.
+    pub fn get_rest_api_tls_identity_path(&self) -> Option<PathBuf> {
+        if self.rest_api.tls_identity_path.as_os_str().is_empty() {
+            None
+        } else {
+            Some(self.rest_api.tls_identity_path.clone())
+        }
+    }
+
+    /// Password protecting the PKCS #12 identity, taken from the environment.
+    /// Archives without a password don't need it to be set.
+    pub fn get_rest_api_tls_identity_password(&self) -> String {
+        std::env::var(TLS_IDENTITY_PASSWORD_ENV).unwrap_or_default()
+    }
+
+    pub fn get_rest_api_report_submissions_per_minute(&self) -> usize {
+        self.rest_api.report_submissions_per_minute
+    }
+
+    pub fn get_rest_api_json_rpc_enabled(&self) -> bool {
+        self.rest_api.json_rpc_enabled
+    }
+
+    pub fn get_tendermint_enabled(&self) -> bool {
+        self.tendermint.enabled
+    }
+
+    pub fn get_abci_address(&self) -> SocketAddr {
+        self.tendermint.abci_address
+    }
+
+    pub fn get_mix_mining_database_path(&self) -> PathBuf {
+        // configs written before the option existed don't specify the database location
+        if self.mix_mining.database_path.as_os_str().is_empty() {
+            MixMining::default_database_path(&self.validator.id)
+        } else {
+            self.mix_mining.database_path.clone()
+        }
+    }
+
+    pub fn get_mix_mining_authorized_monitors(&self) -> Vec<String> {
+        self.mix_mining.authorized_monitors.clone()
+    }
+
+    pub fn get_mix_mining_measurement_history_depth(&self) -> usize {
+        self.mix_mining.measurement_history_depth
+    }
+
+    pub fn get_mix_mining_reliability_smoothing_factor(&self) -> f64 {
+        self.mix_mining.reliability_smoothing_factor
+    }
+
+    pub fn get_mix_mining_reliability_smoothing_reset_after(&self) -> u64 {
+        self.mix_mining.reliability_smoothing_reset_after
+    }
+
+    pub fn get_mix_mining_reliability_threshold(&self) -> f64 {
+        self.mix_mining.reliability_threshold
+    }
+
+    pub fn get_mix_mining_underperforming_sweeps(&self) -> usize {
+        self.mix_mining.underperforming_sweeps
+    }
+
+    pub fn get_mix_mining_mix_layers(&self) -> u64 {
+        self.mix_mining.mix_layers
+    }
+
+    pub fn get_mix_mining_minimum_layer_size(&self) -> usize {
+        self.mix_mining.minimum_layer_size
+    }
+
+    pub fn get_mix_mining_layer_balance_max_age(&self) -> u64 {
+        self.mix_mining.layer_balance_max_age
+    }
+
     #[allow(dead_code)]
     pub fn get_location(&self) -> String {
         self.validator.location.clone()
@@ -166,7 +265,43 @@ impl Default for Validator {
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
-#[serde(deny_unknown_fields)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestApi {
+    /// Socket address to which the REST API will bind to and will be listening for requests.
+    listening_address: SocketAddr,
+
+    /// Optional path to a PKCS #12 archive containing the certificate chain and private key.
+    /// If specified, the REST API is served over https rather than plain http.
+    /// The TLS support of the underlying web server can only load PKCS #12 archives, so a PEM
+    /// certificate and key have to be bundled up first, for example with `openssl pkcs12 -export`.
+    /// The password protecting the archive is read from the `NYM_VALIDATOR_TLS_IDENTITY_PASSWORD`
+    /// environment variable rather than stored here.
+    tls_identity_path: PathBuf,
+
+    /// Maximum number of network monitor reports a single address may submit per minute.
+    /// Further submissions are rejected until enough time has passed.
+    report_submissions_per_minute: usize,
+
+    /// Whether the JSON-RPC interface to the mixmining data is served at `/rpc`
+    /// alongside the REST API. It's disabled unless explicitly turned on.
+    json_rpc_enabled: bool,
+}
+
+impl Default for RestApi {
+    fn default() -> Self {
+        RestApi {
+            listening_address: format!("127.0.0.1:{}", DEFAULT_REST_API_PORT)
+                .parse()
+                .unwrap(),
+            tls_identity_path: Default::default(),
+            report_submissions_per_minute: rest::DEFAULT_REPORT_SUBMISSIONS_PER_MINUTE,
+            json_rpc_enabled: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct MixMining {
     /// Directory server from which the validator will obtain initial topology.
     directory_server: String,
@@ -187,6 +322,52 @@ pub struct MixMining {
 
     /// How many packets should be sent through each path during the mix-mining procedure.
     number_of_test_packets: u64,
+
+    /// Path to the directory containing the database with mix-mining data, such as
+    /// the reputation of all measured mixnodes.
+    database_path: PathBuf,
+
+    /// Number of most recent measurements kept for each mixnode.
+    measurement_history_depth: usize,
+
+    /// Weight (between 0 and 1) of the newest measurement in the smoothed reliability
+    /// of a mixnode. The higher it is, the quicker the smoothed value follows recent behaviour.
+    reliability_smoothing_factor: f64,
+
+    /// How long a mixnode can go without being measured before its smoothed reliability is
+    /// started over, rather than updated, once it reappears.
+    /// The provided value is interpreted as milliseconds.
+    reliability_smoothing_reset_after: u64,
+
+    /// Reliability (fraction of test packets that made it through) below which a measurement
+    /// of a mixnode is considered bad.
+    reliability_threshold: f64,
+
+    /// Number of consecutive bad measurements after which a mixnode is reported as
+    /// underperforming. It can't exceed `measurement_history_depth`.
+    underperforming_sweeps: usize,
+
+    /// Number of layers the mixnet is made of.
+    mix_layers: u64,
+
+    /// Number of healthy mixnodes a layer needs not to be reported as underpopulated.
+    minimum_layer_size: usize,
+
+    /// How long ago a mixnode can have last been measured and still count towards
+    /// the population of its layer.
+    /// The provided value is interpreted as milliseconds.
+    layer_balance_max_age: u64,
+
+    /// Base58-encoded identity public keys of the network monitors allowed to submit
+    /// measurement reports over the REST API. Anything signed by anyone else is rejected.
+    /// Tendermint transactions are only accepted from the monitors set in the genesis file.
+    authorized_monitors: Vec<String>,
+}
+
+impl MixMining {
+    fn default_database_path(id: &str) -> PathBuf {
+        Config::default_data_directory(Some(id)).join("mixmining.db")
+    }
 }
 
 impl Default for MixMining {
@@ -197,17 +378,39 @@ impl Default for MixMining {
             resolution_timeout: DEFAULT_MIX_MINING_RESOLUTION_TIMEOUT,
             number_of_test_packets: DEFAULT_NUMBER_OF_MIX_MINING_TEST_PACKETS,
             connection_timeout: DEFAULT_MIX_MINING_CONNECTION_TIMEOUT,
+            database_path: Default::default(),
+            measurement_history_depth: mixmining::db::DEFAULT_HISTORY_DEPTH,
+            reliability_smoothing_factor: mixmining::db::DEFAULT_SMOOTHING_FACTOR,
+            reliability_smoothing_reset_after: mixmining::db::DEFAULT_SMOOTHING_RESET_AFTER,
+            reliability_threshold: mixmining::DEFAULT_RELIABILITY_THRESHOLD,
+            underperforming_sweeps: mixmining::DEFAULT_UNDERPERFORMING_SWEEPS,
+            mix_layers: mixmining::DEFAULT_MIX_LAYERS,
+            minimum_layer_size: mixmining::DEFAULT_MINIMUM_LAYER_SIZE,
+            layer_balance_max_age: mixmining::DEFAULT_LAYER_BALANCE_MAX_AGE,
+            authorized_monitors: Vec::new(),
         }
     }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
-#[serde(deny_unknown_fields)]
-pub struct Tendermint {}
+#[serde(default, deny_unknown_fields)]
+pub struct Tendermint {
+    /// Whether the mixmining measurements are obtained through Tendermint. If so, the ABCI
+    /// application is started and monitor reports are no longer accepted over the REST API,
+    /// so that the mixmining state only ever changes as agreed on by the chain.
+    enabled: bool,
+
+    /// Socket address on which the ABCI application will be listening for connections
+    /// from the Tendermint node.
+    abci_address: SocketAddr,
+}
 
 impl Default for Tendermint {
     fn default() -> Self {
-        Tendermint {}
+        Tendermint {
+            enabled: false,
+            abci_address: format!("127.0.0.1:{}", DEFAULT_ABCI_PORT).parse().unwrap(),
+        }
     }
 }
 
@@ -260,4 +463,53 @@ mod validator_config {
 
         assert_eq!(default_config, loaded_config);
     }
+
+    #[test]
+    fn authorized_monitors_survive_saving_and_loading() {
+        let temp_location = tempfile::tempdir().unwrap().path().join("config.toml");
+        let mut config = Config::default().with_id("foomp".to_string());
+        config.mix_mining.authorized_monitors = vec!["abc".to_string(), "def".to_string()];
+        config.save_to_file(Some(temp_location.clone())).unwrap();
+
+        let loaded_config = Config::load_from_file(Some(temp_location), None).unwrap();
+
+        assert_eq!(config, loaded_config);
+    }
+
+    #[test]
+    fn config_without_newer_options_can_still_be_loaded() {
+        let temp_location = tempfile::tempdir().unwrap().path().join("config.toml");
+        std::fs::create_dir_all(temp_location.parent().unwrap()).unwrap();
+        let old_config = r#"
+            [validator]
+            id = 'foomp'
+            location = 'unknown'
+            nym_root_directory = '/tmp/validators'
+
+            [mix_mining]
+            directory_server = 'https://directory.nymtech.net'
+            run_delay = 10000
+            resolution_timeout = 5000
+            connection_timeout = 1500
+            number_of_test_packets = 2
+
+            [tendermint]
+
+            [logging]
+        "#;
+        std::fs::write(&temp_location, old_config).unwrap();
+
+        let loaded_config = Config::load_from_file(Some(temp_location), None).unwrap();
+
+        assert_eq!(RestApi::default(), loaded_config.rest_api);
+        assert_eq!(Tendermint::default(), loaded_config.tendermint);
+        assert_eq!(
+            mixmining::db::DEFAULT_HISTORY_DEPTH,
+            loaded_config.get_mix_mining_measurement_history_depth()
+        );
+        assert_eq!(
+            MixMining::default_database_path("foomp"),
+            loaded_config.get_mix_mining_database_path()
+        );
+    }
 }
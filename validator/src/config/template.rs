@@ -40,6 +40,30 @@ location = '{{ validator.location }}'
 nym_root_directory = '{{ validator.nym_root_directory }}'
 
 
+##### REST API config options #####
+
+[rest_api]
+
+# Socket address to which the REST API will bind to and will be listening for requests.
+listening_address = '{{ rest_api.listening_address }}'
+
+# Optional path to a PKCS #12 archive containing the certificate chain and private key.
+# If specified, the REST API is served over https rather than plain http.
+# The TLS support of the underlying web server can only load PKCS #12 archives, so a PEM
+# certificate and key have to be bundled up first, for example with `openssl pkcs12 -export`.
+# The password protecting the archive is read from the `NYM_VALIDATOR_TLS_IDENTITY_PASSWORD`
+# environment variable rather than stored here.
+tls_identity_path = '{{ rest_api.tls_identity_path }}'
+
+# Maximum number of network monitor reports a single address may submit per minute.
+# Further submissions are rejected until enough time has passed.
+report_submissions_per_minute = {{ rest_api.report_submissions_per_minute }}
+
+# Whether the JSON-RPC interface to the mix-mining data is served at `/rpc`
+# alongside the REST API. It's disabled unless explicitly turned on.
+json_rpc_enabled = {{ rest_api.json_rpc_enabled }}
+
+
 ##### mix mining config options #####
 
 [mix_mining]
@@ -64,11 +88,59 @@ connection_timeout =  {{ mix_mining.connection_timeout }}
 # How many packets should be sent through each path during the mix-mining procedure.
 number_of_test_packets = {{ mix_mining.number_of_test_packets }}
 
+# Path to the directory containing the database with mix-mining data, such as
+# the reputation of all measured mixnodes.
+database_path = '{{ mix_mining.database_path }}'
+
+# Number of most recent measurements kept for each mixnode.
+measurement_history_depth = {{ mix_mining.measurement_history_depth }}
+
+# Weight (between 0 and 1) of the newest measurement in the smoothed reliability
+# of a mixnode. The higher it is, the quicker the smoothed value follows recent behaviour.
+reliability_smoothing_factor = {{ mix_mining.reliability_smoothing_factor }}
+
+# How long a mixnode can go without being measured before its smoothed reliability is
+# started over, rather than updated, once it reappears.
+# The provided value is interpreted as milliseconds.
+reliability_smoothing_reset_after = {{ mix_mining.reliability_smoothing_reset_after }}
+
+# Reliability (fraction of test packets that made it through) below which a measurement
+# of a mixnode is considered bad.
+reliability_threshold = {{ mix_mining.reliability_threshold }}
+
+# Number of consecutive bad measurements after which a mixnode is reported as
+# underperforming. It can't exceed `measurement_history_depth`.
+underperforming_sweeps = {{ mix_mining.underperforming_sweeps }}
+
+# Number of layers the mixnet is made of.
+mix_layers = {{ mix_mining.mix_layers }}
+
+# Number of healthy mixnodes a layer needs not to be reported as underpopulated.
+minimum_layer_size = {{ mix_mining.minimum_layer_size }}
+
+# How long ago a mixnode can have last been measured and still count towards
+# the population of its layer.
+# The provided value is interpreted as milliseconds.
+layer_balance_max_age = {{ mix_mining.layer_balance_max_age }}
+
+# Base58-encoded identity public keys of the network monitors allowed to submit
+# measurement reports over the REST API. Anything signed by anyone else is rejected.
+# Tendermint transactions are only accepted from the monitors set in the genesis file.
+authorized_monitors = [{{#each mix_mining.authorized_monitors}}'{{this}}', {{/each}}]
+
 
 ##### tendermint config options #####
 
 [tendermint]
 
+# Whether the mix-mining measurements are obtained through Tendermint. If so, the ABCI
+# application is started and monitor reports are no longer accepted over the REST API,
+# so that the mix-mining state only ever changes as agreed on by the chain.
+enabled = {{ tendermint.enabled }}
+
+# Socket address on which the ABCI application will be listening for connections
+# from the Tendermint node.
+abci_address = '{{ tendermint.abci_address }}'
 
 
 ##### logging configuration options #####
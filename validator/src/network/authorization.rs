@@ -0,0 +1,127 @@
+use crypto::asymmetric::identity;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum AuthorizationError {
+    MalformedPublicKey,
+    MalformedSignature,
+    UnknownMonitor,
+    InvalidSignature,
+}
+
+impl fmt::Display for AuthorizationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthorizationError::MalformedPublicKey => write!(f, "Malformed monitor public key"),
+            AuthorizationError::MalformedSignature => write!(f, "Malformed monitor signature"),
+            AuthorizationError::UnknownMonitor => write!(f, "Monitor is not authorized"),
+            AuthorizationError::InvalidSignature => write!(f, "Invalid monitor signature"),
+        }
+    }
+}
+
+/// Public keys of the network monitors whose measurements are accepted by the validator,
+/// whether they're submitted as reports over the REST API or as Tendermint transactions.
+/// The submitted data has to be signed, exactly as sent over the wire, by one of them.
+#[derive(Clone, Debug, Default)]
+pub struct AuthorizedMonitors {
+    keys: Vec<identity::PublicKey>,
+}
+
+impl AuthorizedMonitors {
+    pub fn new(keys: Vec<identity::PublicKey>) -> AuthorizedMonitors {
+        AuthorizedMonitors { keys }
+    }
+
+    /// Checks that `signature` is a valid signature of `data` made by an authorized monitor
+    /// identified by `public_key`. Both the key and the signature are base58-encoded.
+    pub fn verify(
+        &self,
+        public_key: &str,
+        signature: &str,
+        data: &[u8],
+    ) -> Result<(), AuthorizationError> {
+        let public_key = identity::PublicKey::from_base58_string(public_key)
+            .map_err(|_| AuthorizationError::MalformedPublicKey)?;
+        if !self.keys.contains(&public_key) {
+            return Err(AuthorizationError::UnknownMonitor);
+        }
+
+        let signature = identity::Signature::from_base58_string(signature)
+            .map_err(|_| AuthorizationError::MalformedSignature)?;
+        public_key
+            .verify(data, &signature)
+            .map_err(|_| AuthorizationError::InvalidSignature)
+    }
+}
+
+#[cfg(test)]
+mod verifying_signatures {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"timestamp":1234,"results":[]}"#;
+
+    fn sign(keypair: &identity::KeyPair, body: &[u8]) -> String {
+        keypair.private_key().sign(body).to_base58_string()
+    }
+
+    #[test]
+    fn data_signed_by_authorized_monitor_is_accepted() {
+        let keypair = identity::KeyPair::new();
+        let monitors = AuthorizedMonitors::new(vec![*keypair.public_key()]);
+        let public_key = keypair.public_key().to_base58_string();
+
+        assert_eq!(
+            Ok(()),
+            monitors.verify(&public_key, &sign(&keypair, BODY), BODY)
+        );
+    }
+
+    #[test]
+    fn data_signed_by_unknown_monitor_is_rejected() {
+        let keypair = identity::KeyPair::new();
+        let monitors = AuthorizedMonitors::new(vec![*identity::KeyPair::new().public_key()]);
+        let public_key = keypair.public_key().to_base58_string();
+
+        assert_eq!(
+            Err(AuthorizationError::UnknownMonitor),
+            monitors.verify(&public_key, &sign(&keypair, BODY), BODY)
+        );
+    }
+
+    #[test]
+    fn tampered_data_is_rejected() {
+        let keypair = identity::KeyPair::new();
+        let monitors = AuthorizedMonitors::new(vec![*keypair.public_key()]);
+        let public_key = keypair.public_key().to_base58_string();
+        let tampered = br#"{"timestamp":1235,"results":[]}"#;
+
+        assert_eq!(
+            Err(AuthorizationError::InvalidSignature),
+            monitors.verify(&public_key, &sign(&keypair, BODY), tampered)
+        );
+    }
+
+    #[test]
+    fn malformed_public_key_is_rejected() {
+        let keypair = identity::KeyPair::new();
+        let monitors = AuthorizedMonitors::new(vec![*keypair.public_key()]);
+
+        assert_eq!(
+            Err(AuthorizationError::MalformedPublicKey),
+            monitors.verify("not-base58!", &sign(&keypair, BODY), BODY)
+        );
+    }
+
+    #[test]
+    fn malformed_signature_is_rejected() {
+        let keypair = identity::KeyPair::new();
+        let monitors = AuthorizedMonitors::new(vec![*keypair.public_key()]);
+        let public_key = keypair.public_key().to_base58_string();
+
+        assert_eq!(
+            Err(AuthorizationError::MalformedSignature),
+            monitors.verify(&public_key, "abc", BODY)
+        );
+    }
+}
@@ -15,5 +15,6 @@
 //! The `network` module provides interfaces to external systems via network
 //! connectivity.
 //!
+pub mod authorization;
 pub mod rest;
 pub mod tendermint;
@@ -30,8 +30,16 @@ impl Handler for Update {
         match json_parse {
             Ok(capacity) => {
                 let capacity = capacity.expect("Unexpected JSON parsing problem").value;
-                self.service.lock().unwrap().set_capacity(capacity);
-                Ok(Response::with(status::Created))
+                match self.service.lock().unwrap().set_capacity(capacity) {
+                    Ok(_) => Ok(Response::with(status::Created)),
+                    Err(err) => {
+                        error!("Failed to record capacity - {}", err);
+                        Ok(Response::with((
+                            status::InternalServerError,
+                            "Failed to record the capacity",
+                        )))
+                    }
+                }
             }
             Err(err) => Ok(Response::with((status::BadRequest, err.detail))),
         }
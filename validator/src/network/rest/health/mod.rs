@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use iron::mime::Mime;
+use iron::status;
+use iron::Handler;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Holds data for the validator health report (json)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+    // unknown if the validator doesn't run the Tendermint ABCI at all
+    abci_ready: Option<bool>,
+    mixmining_db_reachable: bool,
+    last_measurement_recorded: Option<u64>,
+}
+
+impl Health {
+    fn is_healthy(&self) -> bool {
+        self.abci_ready.unwrap_or(true) && self.mixmining_db_reachable
+    }
+}
+
+/// Reports whether the validator is ready to serve requests. Responds with 200 once both
+/// the mixmining service and, if it's enabled, the Tendermint ABCI are up, and with 503 otherwise.
+pub struct Get {
+    service: Arc<Mutex<mixmining::Service>>,
+    abci_ready: Option<Arc<AtomicBool>>,
+}
+
+impl Get {
+    pub fn new(
+        service: Arc<Mutex<mixmining::Service>>,
+        abci_ready: Option<Arc<AtomicBool>>,
+    ) -> Get {
+        Get {
+            service,
+            abci_ready,
+        }
+    }
+}
+
+impl Handler for Get {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let health = {
+            let service = self.service.lock().unwrap();
+            Health {
+                abci_ready: self
+                    .abci_ready
+                    .as_ref()
+                    .map(|abci_ready| abci_ready.load(Ordering::SeqCst)),
+                mixmining_db_reachable: service.is_db_reachable(),
+                last_measurement_recorded: service.last_measurement_recorded(),
+            }
+        };
+        let status = if health.is_healthy() {
+            status::Ok
+        } else {
+            status::ServiceUnavailable
+        };
+        let json = serde_json::to_string(&health).unwrap();
+        Ok(Response::with((content_type, status, json)))
+    }
+}
+
+#[cfg(test)]
+mod health_status {
+    use super::*;
+
+    fn health(abci_ready: Option<bool>, mixmining_db_reachable: bool) -> Health {
+        Health {
+            abci_ready,
+            mixmining_db_reachable,
+            last_measurement_recorded: None,
+        }
+    }
+
+    #[test]
+    fn is_healthy_once_everything_is_up() {
+        assert!(health(Some(true), true).is_healthy());
+    }
+
+    #[test]
+    fn is_unhealthy_until_abci_is_ready() {
+        assert!(!health(Some(false), true).is_healthy());
+    }
+
+    #[test]
+    fn abci_does_not_count_when_it_is_not_running() {
+        assert!(health(None, true).is_healthy());
+        assert!(!health(None, false).is_healthy());
+    }
+}
@@ -11,42 +11,157 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use crate::network::authorization::AuthorizedMonitors;
 use crate::services::mixmining;
+use crypto::asymmetric::identity;
+use hyper_native_tls::NativeTlsServer;
 use iron::prelude::*;
+use log::error;
+use monitor::report;
 use presence::mixnode;
 use presence::topology;
+use rate_limit::RateLimit;
+use reputation::history;
+use reputation::layers;
+use reputation::list;
+use reputation::underperforming;
 use router::Router;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
 
 mod capacity;
+mod health;
+mod monitor;
 mod presence;
+mod rate_limit;
+mod reputation;
+mod rpc;
 mod staking;
 
+/// How many reports each source may submit per minute, unless configured otherwise.
+pub const DEFAULT_REPORT_SUBMISSIONS_PER_MINUTE: usize = 6;
+
+/// Identity used for serving the REST API over https: a PKCS #12 archive containing
+/// the certificate chain and the private key, along with the password protecting it.
+/// Iron only supports TLS through `hyper-native-tls`, which can't load PEM files directly.
+pub struct TlsIdentity {
+    pub path: PathBuf,
+    pub password: String,
+}
+
 pub struct Api {
+    listening_address: SocketAddr,
     mixmining_service: Arc<Mutex<mixmining::Service>>,
+    abci_ready: Option<Arc<AtomicBool>>,
+    report_submissions_per_minute: usize,
+    authorized_monitors: AuthorizedMonitors,
+    tls_identity: Option<TlsIdentity>,
+    json_rpc_enabled: bool,
+    report_submissions_enabled: bool,
 }
 
 impl Api {
-    pub fn new(mixmining_service: mixmining::Service) -> Api {
-        let service = Arc::new(Mutex::new(mixmining_service));
+    pub fn new(
+        listening_address: SocketAddr,
+        mixmining_service: Arc<Mutex<mixmining::Service>>,
+    ) -> Api {
         Api {
-            mixmining_service: service,
+            listening_address,
+            mixmining_service,
+            abci_ready: None,
+            report_submissions_per_minute: DEFAULT_REPORT_SUBMISSIONS_PER_MINUTE,
+            authorized_monitors: AuthorizedMonitors::default(),
+            tls_identity: None,
+            json_rpc_enabled: false,
+            report_submissions_enabled: true,
         }
     }
 
-    /// Run the REST API.
-    pub async fn run(self) {
-        let port = 3000; // TODO: make this configurable
-        let address = format!("localhost:{}", port);
-        println!("* starting REST API on http://{}", address);
+    /// Limit how many monitor reports a single source address may submit per minute.
+    pub fn with_report_rate_limit(mut self, submissions_per_minute: usize) -> Self {
+        self.report_submissions_per_minute = submissions_per_minute;
+        self
+    }
+
+    /// Accept monitor reports signed by any of the given keys. Without any authorized monitors,
+    /// all submitted reports are rejected.
+    pub fn with_authorized_monitors(mut self, keys: Vec<identity::PublicKey>) -> Self {
+        self.authorized_monitors = AuthorizedMonitors::new(keys);
+        self
+    }
 
-        let router = self.setup_router();
+    /// Only report the validator as healthy once `abci_ready` is set, which happens
+    /// as soon as the Tendermint ABCI is up.
+    pub fn with_abci_readiness(mut self, abci_ready: Arc<AtomicBool>) -> Self {
+        self.abci_ready = Some(abci_ready);
+        self
+    }
 
-        Iron::new(router).http(address).unwrap();
+    /// Serve the API over https rather than plain http.
+    pub fn with_tls(mut self, identity: TlsIdentity) -> Self {
+        self.tls_identity = Some(identity);
+        self
+    }
+
+    /// Additionally serve the JSON-RPC interface to the mixmining data at `/rpc`.
+    pub fn with_json_rpc(mut self) -> Self {
+        self.json_rpc_enabled = true;
+        self
+    }
+
+    /// Don't accept monitor reports over the API, neither over REST nor over JSON-RPC.
+    /// Used when the measurements come through Tendermint instead.
+    pub fn without_report_submissions(mut self) -> Self {
+        self.report_submissions_enabled = false;
+        self
+    }
+
+    /// Run the REST API. This blocks the current thread for as long as the API is running.
+    /// `ready` is notified once the API is listening for requests. If the API fails to start,
+    /// for example because the port is already taken, the error is logged and `ready` is dropped.
+    pub fn run(self, ready: oneshot::Sender<()>) {
+        let address = self.listening_address;
+        let iron = Iron::new(self.setup_router());
+
+        let listening = match self.tls_identity {
+            None => {
+                println!("* starting REST API on http://{}", address);
+                iron.http(address)
+            }
+            Some(identity) => {
+                let ssl = match NativeTlsServer::new(&identity.path, &identity.password) {
+                    Ok(ssl) => ssl,
+                    Err(err) => {
+                        error!(
+                            "Failed to load the REST API TLS identity from {:?} - {}",
+                            identity.path, err
+                        );
+                        return;
+                    }
+                };
+                println!("* starting REST API on https://{}", address);
+                iron.https(address, ssl)
+            }
+        };
+
+        match listening {
+            // the returned guard joins the server threads once it's dropped,
+            // so we're going to stay here for as long as the server is running
+            Ok(_listening) => {
+                let _ = ready.send(());
+            }
+            Err(err) => error!(
+                "Failed to start the REST API on {} (is the port already in use?) - {}",
+                address, err
+            ),
+        }
     }
 
     /// Tie together URL route paths with handler functions.
-    fn setup_router(self) -> Router {
+    fn setup_router(&self) -> Router {
         // define a Router to hold our routes
         let mut router = Router::new();
 
@@ -56,16 +171,57 @@ impl Api {
         let presence_mixnode_create =
             mixnode::CreatePresence::new(Arc::clone(&self.mixmining_service));
         let topology_get = topology::GetTopology::new(Arc::clone(&self.mixmining_service));
+        // reports submitted over REST and JSON-RPC count towards the same limit
+        let report_rate_limit = Arc::new(RateLimit::new(self.report_submissions_per_minute));
+        let mut mixmining_report_create = Chain::new(report::CreateReport::new(
+            Arc::clone(&self.mixmining_service),
+            self.authorized_monitors.clone(),
+        ));
+        mixmining_report_create.link_before(Arc::clone(&report_rate_limit));
+        let mixnodes_list = list::ListMixnodes::new(Arc::clone(&self.mixmining_service));
+        let mixnodes_underperforming =
+            underperforming::ListUnderperforming::new(Arc::clone(&self.mixmining_service));
+        let mixnode_history = history::GetHistory::new(Arc::clone(&self.mixmining_service));
+        let mixnet_layers = layers::ListLayers::new(Arc::clone(&self.mixmining_service));
+        let health_get =
+            health::Get::new(Arc::clone(&self.mixmining_service), self.abci_ready.clone());
 
         // tie routes to handlers
         router.get("/capacity", capacity_get, "capacity_get");
         router.post("/capacity", capacity_update, "capacity_update");
         router.get("/topology", topology_get, "topology_get");
+        router.get("/mixnodes", mixnodes_list, "mixnodes_list");
+        router.get(
+            "/mixnodes/underperforming",
+            mixnodes_underperforming,
+            "mixnodes_underperforming",
+        );
+        router.get("/mixnodes/layers", mixnet_layers, "mixnet_layers");
+        router.get("/mixnodes/:pubkey", mixnode_history, "mixnode_history");
+        router.get("/health", health_get, "health_get");
         router.post(
             "/presence/mixnodes",
             presence_mixnode_create,
             "presence_mixnodes_post",
         );
+        if self.report_submissions_enabled {
+            router.post(
+                "/mixmining/reports",
+                mixmining_report_create,
+                "mixmining_reports_post",
+            );
+        }
+        if self.json_rpc_enabled {
+            let mut json_rpc = rpc::JsonRpc::new(
+                Arc::clone(&self.mixmining_service),
+                self.authorized_monitors.clone(),
+                report_rate_limit,
+            );
+            if !self.report_submissions_enabled {
+                json_rpc = json_rpc.without_report_submissions();
+            }
+            router.post("/rpc", json_rpc, "json_rpc_post");
+        }
 
         router
     }
@@ -0,0 +1,61 @@
+use crate::network::rest::monitor::models::MonitorReport as RestReport;
+use crate::network::rest::monitor::models::NodeTestResult as RestNodeTestResult;
+use crate::services::mixmining::models::NodeTestResult as ServiceNodeTestResult;
+use crate::services::mixmining::models::Report as ServiceReport;
+use std::convert::From;
+
+impl From<RestNodeTestResult> for ServiceNodeTestResult {
+    fn from(value: RestNodeTestResult) -> ServiceNodeTestResult {
+        ServiceNodeTestResult {
+            public_key: value.pub_key,
+            layer: value.layer,
+            packets_sent: value.packets_sent,
+            packets_received: value.packets_received,
+        }
+    }
+}
+
+impl From<RestReport> for ServiceReport {
+    fn from(value: RestReport) -> ServiceReport {
+        ServiceReport {
+            timestamp: value.timestamp,
+            results: value.results.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_monitor_conversions_for_mixmining_service {
+    use super::*;
+
+    fn rest_node_test_result_fixture() -> RestNodeTestResult {
+        RestNodeTestResult {
+            pub_key: "abc".to_owned(),
+            layer: 2,
+            packets_sent: 10,
+            packets_received: 9,
+        }
+    }
+
+    #[test]
+    fn test_building_service_report_from_rest_report() {
+        let rest_result = rest_node_test_result_fixture();
+        let rest_report = RestReport {
+            timestamp: 1234,
+            results: vec![rest_result.clone()],
+        };
+
+        let service_report = ServiceReport::from(rest_report);
+        assert_eq!(1234, service_report.timestamp);
+        assert_eq!(1, service_report.results.len());
+
+        let service_result = &service_report.results[0];
+        assert_eq!(service_result.public_key, rest_result.pub_key);
+        assert_eq!(service_result.layer, rest_result.layer);
+        assert_eq!(service_result.packets_sent, rest_result.packets_sent);
+        assert_eq!(
+            service_result.packets_received,
+            rest_result.packets_received
+        );
+    }
+}
@@ -0,0 +1,5 @@
+use super::*;
+
+mod conversions;
+pub mod models;
+pub mod report;
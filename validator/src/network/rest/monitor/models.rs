@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeTestResult {
+    pub pub_key: String,
+    pub layer: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+/// Results of a single network monitor run, as submitted by the monitor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorReport {
+    pub timestamp: u64,
+    pub results: Vec<NodeTestResult>,
+}
@@ -0,0 +1,97 @@
+use super::*;
+use crate::network::rest::monitor::models::MonitorReport;
+use crate::services::mixmining::db::MixminingDbError;
+use bodyparser::Raw;
+use iron::status;
+use iron::Handler;
+
+/// Accepts the results of a network monitor run and records them as measurements
+/// of the tested mixnodes. Reports have to be signed by one of the authorized monitors,
+/// otherwise they're rejected with 401. Reports with results no monitor could have observed,
+/// such as more packets received than sent, are rejected with 400. Reports which aren't newer
+/// than the last one accepted from the same monitor are rejected with 409.
+pub struct CreateReport {
+    service: Arc<Mutex<mixmining::Service>>,
+    authorized_monitors: AuthorizedMonitors,
+}
+
+/// Header carrying the base58-encoded public key of the monitor submitting a report.
+pub const PUBLIC_KEY_HEADER: &str = "Nym-Monitor-Key";
+/// Header carrying the base58-encoded signature of the submitted report body.
+pub const SIGNATURE_HEADER: &str = "Nym-Monitor-Signature";
+
+impl CreateReport {
+    pub fn new(
+        service: Arc<Mutex<mixmining::Service>>,
+        authorized_monitors: AuthorizedMonitors,
+    ) -> CreateReport {
+        CreateReport {
+            service,
+            authorized_monitors,
+        }
+    }
+}
+
+fn header_value(req: &Request, name: &str) -> Result<String, String> {
+    req.headers
+        .get_raw(name)
+        .and_then(|values| values.first())
+        .and_then(|value| String::from_utf8(value.clone()).ok())
+        .ok_or_else(|| format!("Missing {} header", name))
+}
+
+// Returns the public key of the monitor that signed the report.
+fn verify(
+    req: &Request,
+    authorized_monitors: &AuthorizedMonitors,
+    body: &str,
+) -> Result<String, String> {
+    let public_key = header_value(req, PUBLIC_KEY_HEADER)?;
+    let signature = header_value(req, SIGNATURE_HEADER)?;
+    authorized_monitors
+        .verify(&public_key, &signature, body.as_bytes())
+        .map_err(|err| err.to_string())?;
+    Ok(public_key)
+}
+
+impl Handler for CreateReport {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        // the signature covers the body exactly as it was sent, so it has to be checked
+        // before the report gets parsed
+        let body = match req.get::<Raw>() {
+            Ok(Some(body)) => body,
+            Ok(None) => return Ok(Response::with((status::BadRequest, "Missing report body"))),
+            Err(err) => return Ok(Response::with((status::BadRequest, err.detail))),
+        };
+
+        let monitor = match verify(req, &self.authorized_monitors, &body) {
+            Ok(monitor) => monitor,
+            Err(err) => return Ok(Response::with((status::Unauthorized, err))),
+        };
+
+        match serde_json::from_str::<MonitorReport>(&body) {
+            Ok(report) => match self
+                .service
+                .lock()
+                .unwrap()
+                .apply_report(&monitor, report.into())
+            {
+                Ok(_) => Ok(Response::with(status::Created)),
+                Err(err @ MixminingDbError::InvalidResult(..)) => {
+                    Ok(Response::with((status::BadRequest, err.to_string())))
+                }
+                Err(err @ MixminingDbError::StaleReport(_)) => {
+                    Ok(Response::with((status::Conflict, err.to_string())))
+                }
+                Err(err) => {
+                    error!("Failed to record monitor report - {}", err);
+                    Ok(Response::with((
+                        status::InternalServerError,
+                        "Failed to record the report",
+                    )))
+                }
+            },
+            Err(err) => Ok(Response::with((status::BadRequest, err.to_string()))),
+        }
+    }
+}
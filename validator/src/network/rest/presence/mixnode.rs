@@ -23,14 +23,19 @@ impl Handler for CreatePresence {
         match json_parse {
             Ok(mixnode) => {
                 let mixnode = mixnode.expect("Unexpected JSON parsing problem");
-                self.service
-                    .lock()
-                    .unwrap()
-                    .add(ServiceMixnode::from_rest_mixnode_with_timestamp(
-                        mixnode,
-                        Timestamp::default(),
-                    ));
-                Ok(Response::with(status::Created))
+                let added = self.service.lock().unwrap().add(
+                    ServiceMixnode::from_rest_mixnode_with_timestamp(mixnode, Timestamp::default()),
+                );
+                match added {
+                    Ok(_) => Ok(Response::with(status::Created)),
+                    Err(err) => {
+                        error!("Failed to record mixnode presence - {}", err);
+                        Ok(Response::with((
+                            status::InternalServerError,
+                            "Failed to record the mixnode",
+                        )))
+                    }
+                }
             }
             Err(err) => Ok(Response::with((status::BadRequest, err.detail))),
         }
@@ -0,0 +1,134 @@
+use iron::middleware::BeforeMiddleware;
+use iron::prelude::*;
+use iron::status;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct RateLimitExceeded(IpAddr);
+
+impl fmt::Display for RateLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rate limit exceeded for {}", self.0)
+    }
+}
+
+impl std::error::Error for RateLimitExceeded {}
+
+/// Limits how many requests each source address can make per minute. Requests over the limit
+/// are rejected with 429 before they reach the handler.
+pub struct RateLimit {
+    requests_per_minute: usize,
+    // times of the requests made by each source within the last minute, oldest first
+    recent_requests: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+}
+
+impl RateLimit {
+    pub fn new(requests_per_minute: usize) -> RateLimit {
+        RateLimit {
+            requests_per_minute,
+            recent_requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request from the given source made at `now`, unless it would exceed the limit.
+    /// Returns whether the request is allowed.
+    pub(super) fn allow(&self, source: IpAddr, now: Instant) -> bool {
+        let mut recent_requests = self.recent_requests.lock().unwrap();
+
+        // forget about sources that have been quiet for a while so the map doesn't grow forever
+        recent_requests.retain(|_, requests| match requests.back() {
+            Some(&latest) => now.duration_since(latest) < WINDOW,
+            None => false,
+        });
+
+        let requests = recent_requests.entry(source).or_default();
+        while let Some(&oldest) = requests.front() {
+            if now.duration_since(oldest) < WINDOW {
+                break;
+            }
+            requests.pop_front();
+        }
+
+        if requests.len() >= self.requests_per_minute {
+            return false;
+        }
+        requests.push_back(now);
+        true
+    }
+}
+
+impl BeforeMiddleware for RateLimit {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let source = req.remote_addr.ip();
+        if self.allow(source, Instant::now()) {
+            Ok(())
+        } else {
+            Err(IronError::new(
+                RateLimitExceeded(source),
+                (
+                    status::TooManyRequests,
+                    "Too many requests, try again later",
+                ),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod rate_limiting {
+    use super::*;
+
+    fn source(last_octet: u8) -> IpAddr {
+        IpAddr::from([10, 0, 0, last_octet])
+    }
+
+    #[test]
+    fn requests_up_to_the_limit_are_allowed() {
+        let rate_limit = RateLimit::new(2);
+        let now = Instant::now();
+        assert!(rate_limit.allow(source(1), now));
+        assert!(rate_limit.allow(source(1), now));
+    }
+
+    #[test]
+    fn requests_over_the_limit_are_rejected() {
+        let rate_limit = RateLimit::new(2);
+        let now = Instant::now();
+        rate_limit.allow(source(1), now);
+        rate_limit.allow(source(1), now);
+        assert!(!rate_limit.allow(source(1), now));
+    }
+
+    #[test]
+    fn sources_are_limited_independently() {
+        let rate_limit = RateLimit::new(1);
+        let now = Instant::now();
+        assert!(rate_limit.allow(source(1), now));
+        assert!(rate_limit.allow(source(2), now));
+        assert!(!rate_limit.allow(source(1), now));
+    }
+
+    #[test]
+    fn requests_are_allowed_again_once_the_window_has_passed() {
+        let rate_limit = RateLimit::new(1);
+        let now = Instant::now();
+        assert!(rate_limit.allow(source(1), now));
+        assert!(!rate_limit.allow(source(1), now + Duration::from_secs(30)));
+        assert!(rate_limit.allow(source(1), now + WINDOW));
+    }
+
+    #[test]
+    fn quiet_sources_are_forgotten() {
+        let rate_limit = RateLimit::new(1);
+        let now = Instant::now();
+        rate_limit.allow(source(1), now);
+        rate_limit.allow(source(2), now + WINDOW);
+        assert_eq!(1, rate_limit.recent_requests.lock().unwrap().len());
+    }
+}
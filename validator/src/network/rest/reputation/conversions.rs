@@ -0,0 +1,110 @@
+use crate::network::rest::reputation::models::MixnetLayer as RestLayer;
+use crate::network::rest::reputation::models::MixnodeMeasurement as RestMeasurement;
+use crate::network::rest::reputation::models::MixnodeReputation as RestReputation;
+use crate::services::mixmining::models::LayerBalance as ServiceLayer;
+use crate::services::mixmining::models::Measurement as ServiceMeasurement;
+use crate::services::mixmining::models::Reputation as ServiceReputation;
+use std::convert::From;
+
+impl From<ServiceMeasurement> for RestMeasurement {
+    fn from(value: ServiceMeasurement) -> RestMeasurement {
+        RestMeasurement {
+            reliability: value.reliability(),
+            timestamp: value.timestamp,
+            packets_sent: value.packets_sent,
+            packets_received: value.packets_received,
+        }
+    }
+}
+
+impl From<ServiceReputation> for RestReputation {
+    fn from(value: ServiceReputation) -> RestReputation {
+        RestReputation {
+            reliability: value.latest.reliability(),
+            smoothed_reliability: value.smoothed_reliability(),
+            last_tested: value.last_tested(),
+            last_healthy: value.last_healthy,
+            pub_key: value.public_key,
+            layer: value.layer,
+            packets_sent: value.latest.packets_sent,
+            packets_received: value.latest.packets_received,
+        }
+    }
+}
+
+impl From<ServiceLayer> for RestLayer {
+    fn from(value: ServiceLayer) -> RestLayer {
+        RestLayer {
+            layer: value.layer,
+            mixnodes: value.mixnodes,
+            healthy_mixnodes: value.healthy_mixnodes,
+            reliability: value.reliability,
+            underpopulated: value.underpopulated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_reputation_conversions_for_mixmining_service {
+    use super::*;
+    use crate::services::mixmining::models::Measurement;
+
+    #[test]
+    fn test_building_rest_measurement_from_service_measurement() {
+        let service_measurement = ServiceMeasurement {
+            timestamp: 1234,
+            packets_sent: 10,
+            packets_received: 5,
+        };
+
+        let rest_measurement = RestMeasurement::from(service_measurement);
+        assert_eq!(rest_measurement.timestamp, 1234);
+        assert_eq!(rest_measurement.reliability, 0.5);
+        assert_eq!(rest_measurement.packets_sent, 10);
+        assert_eq!(rest_measurement.packets_received, 5);
+    }
+
+    #[test]
+    fn test_building_rest_reputation_from_service_reputation() {
+        let service_reputation = ServiceReputation {
+            public_key: "abc".to_owned(),
+            layer: 2,
+            latest: Measurement {
+                timestamp: 1234,
+                packets_sent: 10,
+                packets_received: 9,
+            },
+            recent: vec![],
+            last_healthy: Some(1000),
+            reliability_ema: Some(0.8),
+        };
+
+        let rest_reputation = RestReputation::from(service_reputation.clone());
+        assert_eq!(rest_reputation.pub_key, service_reputation.public_key);
+        assert_eq!(rest_reputation.layer, service_reputation.layer);
+        assert_eq!(rest_reputation.reliability, 0.9);
+        assert_eq!(rest_reputation.smoothed_reliability, 0.8);
+        assert_eq!(rest_reputation.packets_sent, 10);
+        assert_eq!(rest_reputation.packets_received, 9);
+        assert_eq!(rest_reputation.last_tested, 1234);
+        assert_eq!(rest_reputation.last_healthy, Some(1000));
+    }
+
+    #[test]
+    fn test_building_rest_layer_from_service_layer_balance() {
+        let service_layer = ServiceLayer {
+            layer: 2,
+            mixnodes: 3,
+            healthy_mixnodes: 1,
+            reliability: Some(0.25),
+            underpopulated: true,
+        };
+
+        let rest_layer = RestLayer::from(service_layer);
+        assert_eq!(rest_layer.layer, 2);
+        assert_eq!(rest_layer.mixnodes, 3);
+        assert_eq!(rest_layer.healthy_mixnodes, 1);
+        assert_eq!(rest_layer.reliability, Some(0.25));
+        assert!(rest_layer.underpopulated);
+    }
+}
@@ -0,0 +1,42 @@
+use super::*;
+use iron::mime::Mime;
+use iron::status;
+use iron::Handler;
+
+/// Returns the most recent measurements of a single mixnode, identified by its public key,
+/// so that its operator can follow how it has been performing over time.
+/// Responds with 404 if the mixnode has never been measured.
+pub struct GetHistory {
+    service: Arc<Mutex<mixmining::Service>>,
+}
+
+impl GetHistory {
+    pub fn new(service: Arc<Mutex<mixmining::Service>>) -> GetHistory {
+        GetHistory { service }
+    }
+}
+
+impl Handler for GetHistory {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let pub_key = req
+            .extensions
+            .get::<Router>()
+            .and_then(|params| params.find("pubkey"))
+            .unwrap_or_default()
+            .to_owned();
+
+        let history = self.service.lock().unwrap().history(&pub_key);
+        match history {
+            Some(measurements) => {
+                let content_type = "application/json".parse::<Mime>().unwrap();
+                let history = models::MixnodeHistory {
+                    pub_key,
+                    measurements: measurements.into_iter().map(Into::into).collect(),
+                };
+                let json = serde_json::to_string(&history).unwrap();
+                Ok(Response::with((content_type, status::Ok, json)))
+            }
+            None => Ok(Response::with((status::NotFound, "Unknown mixnode"))),
+        }
+    }
+}
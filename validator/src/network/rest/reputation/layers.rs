@@ -0,0 +1,32 @@
+use super::*;
+use iron::mime::Mime;
+use iron::status;
+use iron::Handler;
+
+/// Lists the mixnet layers along with how many (healthy) mixnodes each of them has and
+/// their average reliability, flagging the layers which don't have enough healthy mixnodes.
+pub struct ListLayers {
+    service: Arc<Mutex<mixmining::Service>>,
+}
+
+impl ListLayers {
+    pub fn new(service: Arc<Mutex<mixmining::Service>>) -> ListLayers {
+        ListLayers { service }
+    }
+}
+
+impl Handler for ListLayers {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let layers: Vec<models::MixnetLayer> = self
+            .service
+            .lock()
+            .unwrap()
+            .layer_balance()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let json = serde_json::to_string(&layers).unwrap();
+        Ok(Response::with((content_type, status::Ok, json)))
+    }
+}
@@ -0,0 +1,78 @@
+use super::*;
+use iron::mime::Mime;
+use iron::status;
+use iron::Handler;
+
+/// Number of mixnodes returned when the request doesn't specify a `limit`.
+pub const DEFAULT_LIMIT: usize = 100;
+/// Largest `limit` a client may ask for; bigger values are clamped down to it.
+pub const MAX_LIMIT: usize = 1000;
+
+/// Lists the reputation of every mixnode known to the mixmining service.
+/// The result can be restricted to a single mixnet layer with the `?layer=N` query parameter.
+/// Results are paginated with `?offset=N` (default 0) and `?limit=N` (default 100, max 1000);
+/// the response includes the total number of matching mixnodes.
+pub struct ListMixnodes {
+    service: Arc<Mutex<mixmining::Service>>,
+}
+
+impl ListMixnodes {
+    pub fn new(service: Arc<Mutex<mixmining::Service>>) -> ListMixnodes {
+        ListMixnodes { service }
+    }
+}
+
+fn query_param<T: std::str::FromStr>(req: &Request, name: &str) -> Result<Option<T>, String> {
+    let url: &iron::url::Url = req.url.as_ref();
+    match url.query_pairs().find(|(key, _)| key == name) {
+        None => Ok(None),
+        Some((_, value)) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid {} value '{}'", name, value)),
+    }
+}
+
+struct ListParams {
+    layer: Option<u64>,
+    offset: usize,
+    limit: usize,
+}
+
+fn list_params(req: &Request) -> Result<ListParams, String> {
+    let layer = query_param(req, "layer")?;
+    let offset = query_param(req, "offset")?.unwrap_or(0);
+    let limit = match query_param(req, "limit")?.unwrap_or(DEFAULT_LIMIT) {
+        0 => return Err("Invalid limit value '0'".to_string()),
+        limit => std::cmp::min(limit, MAX_LIMIT),
+    };
+    Ok(ListParams {
+        layer,
+        offset,
+        limit,
+    })
+}
+
+impl Handler for ListMixnodes {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let params = match list_params(req) {
+            Ok(params) => params,
+            Err(err) => return Ok(Response::with((status::BadRequest, err))),
+        };
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let page = self.service.lock().unwrap().reputations_page(
+            params.layer,
+            params.offset,
+            params.limit,
+        );
+        let response = models::MixnodeReputationPage {
+            total: page.total,
+            offset: params.offset,
+            limit: params.limit,
+            mixnodes: page.items.into_iter().map(Into::into).collect(),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        Ok(Response::with((content_type, status::Ok, json)))
+    }
+}
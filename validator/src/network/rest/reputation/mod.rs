@@ -0,0 +1,8 @@
+use super::*;
+
+mod conversions;
+pub mod history;
+pub mod layers;
+pub mod list;
+pub mod models;
+pub mod underperforming;
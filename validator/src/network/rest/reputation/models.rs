@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// Reputation of a single mixnode, as measured by the network monitors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MixnodeReputation {
+    pub pub_key: String,
+    pub layer: u64,
+    pub reliability: f64,
+    pub smoothed_reliability: f64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub last_tested: u64,
+    pub last_healthy: Option<u64>,
+}
+
+/// A single page of mixnode reputations, along with what's needed to fetch the next one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MixnodeReputationPage {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub mixnodes: Vec<MixnodeReputation>,
+}
+
+/// A single measurement of a mixnode, as reported by a network monitor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MixnodeMeasurement {
+    pub timestamp: u64,
+    pub reliability: f64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+/// Most recent measurements of a single mixnode, oldest first.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MixnodeHistory {
+    pub pub_key: String,
+    pub measurements: Vec<MixnodeMeasurement>,
+}
+
+/// How well a single mixnet layer is populated. A layer with too few healthy mixnodes
+/// is flagged as underpopulated, as it's going to be a bottleneck for the whole mixnet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MixnetLayer {
+    pub layer: u64,
+    pub mixnodes: usize,
+    pub healthy_mixnodes: usize,
+    pub reliability: Option<f64>,
+    pub underpopulated: bool,
+}
@@ -0,0 +1,32 @@
+use super::*;
+use iron::mime::Mime;
+use iron::status;
+use iron::Handler;
+
+/// Lists the reputation of mixnodes whose reliability has been below the configured threshold
+/// for each of the last few network monitor sweeps.
+pub struct ListUnderperforming {
+    service: Arc<Mutex<mixmining::Service>>,
+}
+
+impl ListUnderperforming {
+    pub fn new(service: Arc<Mutex<mixmining::Service>>) -> ListUnderperforming {
+        ListUnderperforming { service }
+    }
+}
+
+impl Handler for ListUnderperforming {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let reputations: Vec<models::MixnodeReputation> = self
+            .service
+            .lock()
+            .unwrap()
+            .underperforming()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let json = serde_json::to_string(&reputations).unwrap();
+        Ok(Response::with((content_type, status::Ok, json)))
+    }
+}
@@ -0,0 +1,463 @@
+// Copyright 2020 Nym Technologies SA
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::monitor::models::MonitorReport;
+use super::rate_limit::RateLimit;
+use super::reputation::list::{DEFAULT_LIMIT, MAX_LIMIT};
+use super::reputation::models::{MixnodeReputation, MixnodeReputationPage};
+use super::*;
+use crate::services::mixmining::db::MixminingDbError;
+use bodyparser::Raw;
+use iron::mime::Mime;
+use iron::status;
+use iron::Handler;
+use models::*;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::net::IpAddr;
+use std::time::Instant;
+
+mod models;
+
+/// JSON-RPC 2.0 interface to the mixmining data, for clients which would rather not use REST.
+/// It's backed by the same mixmining service as the REST API, so the two always agree.
+/// Supported methods:
+///
+/// * `getMixnode` with `{"pubKey": ...}` returns the reputation of a single mixnode.
+/// * `listMixnodes` with optional `{"layer": ..., "offset": ..., "limit": ...}` returns a page
+///   of mixnode reputations, just like `GET /mixnodes`.
+/// * `submitReport` with `{"report": ..., "monitorKey": ..., "signature": ...}` records a network
+///   monitor report, just like `POST /mixmining/reports`. `report` is the JSON-encoded report
+///   as a string and the signature has to cover exactly that string. Reports with invalid
+///   results, or which aren't newer than the last one accepted from the same monitor, are
+///   rejected. Submissions count towards the same rate limit as the ones made over REST.
+///   The method is unknown when the API doesn't accept reports at all.
+///
+/// Batches and notifications aren't supported: each request is a single call and gets a response.
+pub struct JsonRpc {
+    service: Arc<Mutex<mixmining::Service>>,
+    authorized_monitors: AuthorizedMonitors,
+    report_rate_limit: Arc<RateLimit>,
+    report_submissions_enabled: bool,
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn to_result<T: serde::Serialize>(value: T) -> Result<Value, RpcError> {
+    Ok(serde_json::to_value(value).expect("mixmining responses are always serializable"))
+}
+
+impl JsonRpc {
+    pub fn new(
+        service: Arc<Mutex<mixmining::Service>>,
+        authorized_monitors: AuthorizedMonitors,
+        report_rate_limit: Arc<RateLimit>,
+    ) -> JsonRpc {
+        JsonRpc {
+            service,
+            authorized_monitors,
+            report_rate_limit,
+            report_submissions_enabled: true,
+        }
+    }
+
+    /// Don't offer the `submitReport` method.
+    pub fn without_report_submissions(mut self) -> Self {
+        self.report_submissions_enabled = false;
+        self
+    }
+
+    /// Handles the raw request body sent from `source`.
+    fn respond(&self, body: &str, source: IpAddr) -> RpcResponse {
+        let value: Value = match serde_json::from_str(body) {
+            Ok(value) => value,
+            Err(err) => {
+                return RpcResponse::error(Value::Null, RpcError::new(PARSE_ERROR, err.to_string()))
+            }
+        };
+        match serde_json::from_value(value) {
+            Ok(request) => self.call(request, source),
+            Err(err) => {
+                RpcResponse::error(Value::Null, RpcError::new(INVALID_REQUEST, err.to_string()))
+            }
+        }
+    }
+
+    fn call(&self, request: RpcRequest, source: IpAddr) -> RpcResponse {
+        if request.jsonrpc != JSON_RPC_VERSION {
+            let error = RpcError::new(INVALID_REQUEST, "Unsupported JSON-RPC version");
+            return RpcResponse::error(request.id, error);
+        }
+
+        let result = match request.method.as_str() {
+            "getMixnode" => self.get_mixnode(request.params),
+            "listMixnodes" => self.list_mixnodes(request.params),
+            "submitReport" if self.report_submissions_enabled => {
+                self.submit_report(request.params, source)
+            }
+            method => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+        };
+        match result {
+            Ok(result) => RpcResponse::result(request.id, result),
+            Err(error) => RpcResponse::error(request.id, error),
+        }
+    }
+
+    fn get_mixnode(&self, params: Value) -> Result<Value, RpcError> {
+        let params: GetMixnodeParams = parse_params(params)?;
+        match self.service.lock().unwrap().reputation(&params.pub_key) {
+            Some(reputation) => to_result(MixnodeReputation::from(reputation)),
+            None => Err(RpcError::new(UNKNOWN_MIXNODE, "Unknown mixnode")),
+        }
+    }
+
+    fn list_mixnodes(&self, params: Value) -> Result<Value, RpcError> {
+        // all of the parameters are optional, so they may just as well be left out entirely
+        let params: ListMixnodesParams = if params.is_null() {
+            ListMixnodesParams::default()
+        } else {
+            parse_params(params)?
+        };
+        let limit = match params.limit.unwrap_or(DEFAULT_LIMIT) {
+            0 => return Err(RpcError::new(INVALID_PARAMS, "Invalid limit value '0'")),
+            limit => std::cmp::min(limit, MAX_LIMIT),
+        };
+
+        let page =
+            self.service
+                .lock()
+                .unwrap()
+                .reputations_page(params.layer, params.offset, limit);
+        to_result(MixnodeReputationPage {
+            total: page.total,
+            offset: params.offset,
+            limit,
+            mixnodes: page.items.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    fn submit_report(&self, params: Value, source: IpAddr) -> Result<Value, RpcError> {
+        if !self.report_rate_limit.allow(source, Instant::now()) {
+            return Err(RpcError::new(
+                RATE_LIMITED,
+                "Too many requests, try again later",
+            ));
+        }
+
+        let params: SubmitReportParams = parse_params(params)?;
+        self.authorized_monitors
+            .verify(
+                &params.monitor_key,
+                &params.signature,
+                params.report.as_bytes(),
+            )
+            .map_err(|err| RpcError::new(UNAUTHORIZED, err.to_string()))?;
+        let report: MonitorReport = serde_json::from_str(&params.report)
+            .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))?;
+
+        self.service
+            .lock()
+            .unwrap()
+            .apply_report(&params.monitor_key, report.into())
+            .map_err(|err| match err {
+                MixminingDbError::InvalidResult(..) => {
+                    RpcError::new(INVALID_PARAMS, err.to_string())
+                }
+                MixminingDbError::StaleReport(_) => RpcError::new(STALE_REPORT, err.to_string()),
+                err => {
+                    error!("Failed to record monitor report - {}", err);
+                    RpcError::new(INTERNAL_ERROR, "Failed to record the report")
+                }
+            })?;
+        Ok(Value::Bool(true))
+    }
+}
+
+impl Handler for JsonRpc {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let source = req.remote_addr.ip();
+        let response = match req.get::<Raw>() {
+            Ok(Some(body)) => self.respond(&body, source),
+            Ok(None) => RpcResponse::error(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, "Missing request body"),
+            ),
+            Err(err) => RpcResponse::error(Value::Null, RpcError::new(PARSE_ERROR, err.detail)),
+        };
+
+        // errors are reported within the JSON-RPC response rather than through the HTTP status
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let json = serde_json::to_string(&response).unwrap();
+        Ok(Response::with((content_type, status::Ok, json)))
+    }
+}
+
+#[cfg(test)]
+mod json_rpc_calls {
+    use super::*;
+    use crate::services::mixmining::db::MixminingDb;
+    use crate::services::mixmining::models::Report;
+    use crate::services::mixmining::tests::{fake_node_test_result, node_key, MONITOR_KEY};
+    use serde_json::json;
+
+    const SOURCE: &str = "10.0.0.1";
+
+    fn new_rpc(monitor: &identity::KeyPair) -> JsonRpc {
+        new_rpc_with_rate_limit(monitor, Arc::new(RateLimit::new(2)))
+    }
+
+    fn new_rpc_with_rate_limit(
+        monitor: &identity::KeyPair,
+        report_rate_limit: Arc<RateLimit>,
+    ) -> JsonRpc {
+        let mut service = mixmining::Service::new(MixminingDb::new());
+        service
+            .apply_report(
+                MONITOR_KEY,
+                Report {
+                    timestamp: 1234,
+                    results: vec![
+                        fake_node_test_result("abc", 1, 9),
+                        fake_node_test_result("def", 2, 9),
+                    ],
+                },
+            )
+            .unwrap();
+        let service = Arc::new(Mutex::new(service));
+        JsonRpc::new(
+            service,
+            AuthorizedMonitors::new(vec![*monitor.public_key()]),
+            report_rate_limit,
+        )
+    }
+
+    fn call(rpc: &JsonRpc, method: &str, params: Value) -> RpcResponse {
+        let request = json!({"jsonrpc": "2.0", "method": method, "params": params, "id": 7});
+        rpc.respond(&request.to_string(), SOURCE.parse().unwrap())
+    }
+
+    fn error_code(response: &RpcResponse) -> i64 {
+        response.error.as_ref().unwrap().code
+    }
+
+    fn report_params(monitor: &identity::KeyPair, timestamp: u64) -> Value {
+        report_params_with_received(monitor, timestamp, 10)
+    }
+
+    fn report_params_with_received(
+        monitor: &identity::KeyPair,
+        timestamp: u64,
+        packets_received: u64,
+    ) -> Value {
+        let report = format!(
+            r#"{{"timestamp":{},"results":[{{"pubKey":"{}","layer":3,"packetsSent":10,"packetsReceived":{}}}]}}"#,
+            timestamp,
+            node_key("ghi"),
+            packets_received
+        );
+        let signature = monitor.private_key().sign(report.as_bytes());
+        json!({
+            "report": report,
+            "monitorKey": monitor.public_key().to_base58_string(),
+            "signature": signature.to_base58_string(),
+        })
+    }
+
+    #[test]
+    fn get_mixnode_returns_its_reputation() {
+        let rpc = new_rpc(&identity::KeyPair::new());
+        let response = call(&rpc, "getMixnode", json!({ "pubKey": node_key("abc") }));
+
+        assert_eq!(json!(7), response.id);
+        let reputation: MixnodeReputation =
+            serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(node_key("abc"), reputation.pub_key);
+        assert_eq!(0.9, reputation.reliability);
+    }
+
+    #[test]
+    fn get_mixnode_fails_for_unknown_mixnode() {
+        let rpc = new_rpc(&identity::KeyPair::new());
+        let response = call(&rpc, "getMixnode", json!({ "pubKey": node_key("xyz") }));
+        assert_eq!(UNKNOWN_MIXNODE, error_code(&response));
+    }
+
+    #[test]
+    fn list_mixnodes_can_be_called_without_params() {
+        let rpc = new_rpc(&identity::KeyPair::new());
+        let request = json!({"jsonrpc": "2.0", "method": "listMixnodes", "id": 1});
+        let response = rpc.respond(&request.to_string(), SOURCE.parse().unwrap());
+
+        let page: MixnodeReputationPage = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(2, page.total);
+        assert_eq!(DEFAULT_LIMIT, page.limit);
+    }
+
+    #[test]
+    fn list_mixnodes_can_be_filtered_by_layer() {
+        let rpc = new_rpc(&identity::KeyPair::new());
+        let response = call(&rpc, "listMixnodes", json!({"layer": 2}));
+
+        let page: MixnodeReputationPage = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(1, page.total);
+        assert_eq!(node_key("def"), page.mixnodes[0].pub_key);
+    }
+
+    #[test]
+    fn list_mixnodes_rejects_zero_limit() {
+        let rpc = new_rpc(&identity::KeyPair::new());
+        let response = call(&rpc, "listMixnodes", json!({"limit": 0}));
+        assert_eq!(INVALID_PARAMS, error_code(&response));
+    }
+
+    #[test]
+    fn submitted_report_signed_by_authorized_monitor_is_recorded() {
+        let monitor = identity::KeyPair::new();
+        let rpc = new_rpc(&monitor);
+        let response = call(&rpc, "submitReport", report_params(&monitor, 2000));
+
+        assert_eq!(Some(json!(true)), response.result);
+        assert!(rpc
+            .service
+            .lock()
+            .unwrap()
+            .reputation(&node_key("ghi"))
+            .is_some());
+    }
+
+    #[test]
+    fn replayed_report_is_rejected() {
+        let monitor = identity::KeyPair::new();
+        let rpc = new_rpc(&monitor);
+        call(&rpc, "submitReport", report_params(&monitor, 2000));
+        let response = call(&rpc, "submitReport", report_params(&monitor, 2000));
+
+        assert_eq!(STALE_REPORT, error_code(&response));
+        assert_eq!(
+            1,
+            rpc.service
+                .lock()
+                .unwrap()
+                .history(&node_key("ghi"))
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn report_with_impossible_result_is_rejected() {
+        let monitor = identity::KeyPair::new();
+        let rpc = new_rpc(&monitor);
+        let response = call(
+            &rpc,
+            "submitReport",
+            report_params_with_received(&monitor, 2000, 11),
+        );
+
+        assert_eq!(INVALID_PARAMS, error_code(&response));
+        assert!(rpc
+            .service
+            .lock()
+            .unwrap()
+            .reputation(&node_key("ghi"))
+            .is_none());
+    }
+
+    #[test]
+    fn submit_report_is_unknown_when_report_submissions_are_disabled() {
+        let monitor = identity::KeyPair::new();
+        let rpc = new_rpc(&monitor).without_report_submissions();
+        let response = call(&rpc, "submitReport", report_params(&monitor, 2000));
+
+        assert_eq!(METHOD_NOT_FOUND, error_code(&response));
+        assert!(rpc
+            .service
+            .lock()
+            .unwrap()
+            .reputation(&node_key("ghi"))
+            .is_none());
+    }
+
+    #[test]
+    fn submitted_report_signed_by_unknown_monitor_is_rejected() {
+        let rpc = new_rpc(&identity::KeyPair::new());
+        let response = call(
+            &rpc,
+            "submitReport",
+            report_params(&identity::KeyPair::new(), 2000),
+        );
+
+        assert_eq!(UNAUTHORIZED, error_code(&response));
+        assert!(rpc
+            .service
+            .lock()
+            .unwrap()
+            .reputation(&node_key("ghi"))
+            .is_none());
+    }
+
+    #[test]
+    fn report_submissions_are_rate_limited() {
+        let monitor = identity::KeyPair::new();
+        let rpc = new_rpc(&monitor);
+        for timestamp in &[2000, 3000] {
+            assert!(
+                call(&rpc, "submitReport", report_params(&monitor, *timestamp))
+                    .error
+                    .is_none()
+            );
+        }
+        let response = call(&rpc, "submitReport", report_params(&monitor, 4000));
+        assert_eq!(RATE_LIMITED, error_code(&response));
+    }
+
+    #[test]
+    fn report_submissions_share_the_rate_limit_with_rest() {
+        let monitor = identity::KeyPair::new();
+        let rate_limit = Arc::new(RateLimit::new(1));
+        let rpc = new_rpc_with_rate_limit(&monitor, Arc::clone(&rate_limit));
+
+        // the only allowed submission was already made over REST
+        assert!(rate_limit.allow(SOURCE.parse().unwrap(), Instant::now()));
+        let response = call(&rpc, "submitReport", report_params(&monitor, 2000));
+        assert_eq!(RATE_LIMITED, error_code(&response));
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let rpc = new_rpc(&identity::KeyPair::new());
+        let source = SOURCE.parse().unwrap();
+
+        assert_eq!(PARSE_ERROR, error_code(&rpc.respond("foomp", source)));
+        assert_eq!(INVALID_REQUEST, error_code(&rpc.respond("[]", source)));
+        let wrong_version = json!({"jsonrpc": "1.0", "method": "listMixnodes", "id": 1});
+        assert_eq!(
+            INVALID_REQUEST,
+            error_code(&rpc.respond(&wrong_version.to_string(), source))
+        );
+        assert_eq!(
+            METHOD_NOT_FOUND,
+            error_code(&call(&rpc, "getEverything", Value::Null))
+        );
+        assert_eq!(
+            INVALID_PARAMS,
+            error_code(&call(&rpc, "getMixnode", json!({"key": "abc"})))
+        );
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2020 Nym Technologies SA
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const JSON_RPC_VERSION: &str = "2.0";
+
+// Error codes defined by the JSON-RPC 2.0 specification.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+// Application specific error codes, from the range the specification reserves for servers.
+pub const UNKNOWN_MIXNODE: i64 = -32001;
+pub const UNAUTHORIZED: i64 = -32002;
+pub const RATE_LIMITED: i64 = -32003;
+pub const STALE_REPORT: i64 = -32004;
+
+/// A single JSON-RPC call. Batches aren't supported.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub id: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new<S: Into<String>>(code: i64, message: S) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Response to a single JSON-RPC call. Exactly one of `result` and `error` is set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: Value,
+}
+
+impl RpcResponse {
+    pub fn result(id: Value, result: Value) -> RpcResponse {
+        RpcResponse {
+            jsonrpc: JSON_RPC_VERSION.to_owned(),
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    pub fn error(id: Value, error: RpcError) -> RpcResponse {
+        RpcResponse {
+            jsonrpc: JSON_RPC_VERSION.to_owned(),
+            result: None,
+            error: Some(error),
+            id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetMixnodeParams {
+    pub pub_key: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ListMixnodesParams {
+    pub layer: Option<u64>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// `report` is the JSON-encoded monitor report exactly as it was signed, so that the same
+/// signature is valid for both the REST API and JSON-RPC submissions.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitReportParams {
+    pub report: String,
+    pub monitor_key: String,
+    pub signature: String,
+}
@@ -0,0 +1,131 @@
+// Copyright 2020 Nym Technologies SA
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::services::mixmining::models::Reputation;
+use crypto::blake3;
+
+// domain separation, so that a leaf can never be mistaken for an inner node or vice versa
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Encodes the part of a reputation which is determined purely by the transactions delivered
+/// through Tendermint. Anything derived using local configuration, such as the length of the
+/// recent history or the smoothed reliability, is left out as it may differ between validators.
+fn encode_leaf(reputation: &Reputation) -> Vec<u8> {
+    let public_key = reputation.public_key.as_bytes();
+    let mut encoded = Vec::with_capacity(1 + 8 + public_key.len() + 5 * 8);
+    encoded.push(LEAF_PREFIX);
+    encoded.extend_from_slice(&(public_key.len() as u64).to_be_bytes());
+    encoded.extend_from_slice(public_key);
+    encoded.extend_from_slice(&reputation.layer.to_be_bytes());
+    encoded.extend_from_slice(&reputation.latest.timestamp.to_be_bytes());
+    encoded.extend_from_slice(&reputation.latest.packets_sent.to_be_bytes());
+    encoded.extend_from_slice(&reputation.latest.packets_received.to_be_bytes());
+    // the most recent healthy measurement can't be older than the epoch, so 0 is free to mean "never"
+    encoded.extend_from_slice(&reputation.last_healthy.unwrap_or(0).to_be_bytes());
+    encoded
+}
+
+fn hash_nodes(left: &blake3::Hash, right: &blake3::Hash) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    hasher.finalize()
+}
+
+/// Merkle root over the given reputations, which have to be ordered by their public keys.
+/// Every validator which delivered the same transactions ends up with the same root,
+/// so it's used as the app hash Tendermint agrees on. No reputations give an empty hash.
+pub fn app_hash(reputations: &[Reputation]) -> Vec<u8> {
+    let mut level: Vec<_> = reputations
+        .iter()
+        .map(|reputation| blake3::hash(&encode_leaf(reputation)))
+        .collect();
+    if level.is_empty() {
+        return Vec::new();
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_nodes(left, right),
+                // the odd one out is carried up to the next level as it is
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level[0].as_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod computing_app_hash {
+    use super::*;
+    use crate::services::mixmining::models::Measurement;
+
+    fn reputation(public_key: &str, packets_received: u64) -> Reputation {
+        Reputation {
+            public_key: public_key.to_owned(),
+            layer: 1,
+            latest: Measurement {
+                timestamp: 1234,
+                packets_sent: 10,
+                packets_received,
+            },
+            recent: vec![],
+            last_healthy: None,
+            reliability_ema: None,
+        }
+    }
+
+    #[test]
+    fn no_reputations_give_empty_hash() {
+        assert!(app_hash(&[]).is_empty());
+    }
+
+    #[test]
+    fn same_reputations_give_same_hash() {
+        let reputations = vec![
+            reputation("abc", 9),
+            reputation("def", 8),
+            reputation("ghi", 7),
+        ];
+        assert_eq!(app_hash(&reputations), app_hash(&reputations.clone()));
+        assert_eq!(32, app_hash(&reputations).len());
+    }
+
+    #[test]
+    fn any_change_gives_different_hash() {
+        let reputations = vec![
+            reputation("abc", 9),
+            reputation("def", 8),
+            reputation("ghi", 7),
+        ];
+        let mut changed = reputations.clone();
+        changed[2].latest.packets_received = 6;
+        assert_ne!(app_hash(&reputations), app_hash(&changed));
+        assert_ne!(app_hash(&reputations), app_hash(&reputations[..2]));
+    }
+
+    #[test]
+    fn locally_derived_data_does_not_affect_hash() {
+        let reputations = vec![reputation("abc", 9), reputation("def", 8)];
+        let mut changed = reputations.clone();
+        changed[0].recent = vec![changed[0].latest.clone()];
+        changed[0].reliability_ema = Some(0.5);
+        assert_eq!(app_hash(&reputations), app_hash(&changed));
+    }
+}
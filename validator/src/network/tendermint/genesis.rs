@@ -0,0 +1,113 @@
+// Copyright 2020 Nym Technologies SA
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::services::mixmining::models::ChainState;
+use crypto::asymmetric::identity;
+use serde::Deserialize;
+use std::fmt;
+
+/// The `app_state` of the Tendermint genesis file. As every validator starts the chain off
+/// with the same genesis, anything affecting which transactions get applied belongs in here
+/// rather than in the local configuration.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GenesisState {
+    // base58-encoded public keys of the monitors allowed to submit transactions
+    #[serde(default)]
+    pub authorized_monitors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum GenesisError {
+    Malformed(serde_json::Error),
+    MonitorPublicKey(String),
+}
+
+impl fmt::Display for GenesisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenesisError::Malformed(err) => write!(f, "malformed genesis app state - {}", err),
+            GenesisError::MonitorPublicKey(key) => {
+                write!(f, "invalid authorized monitor public key '{}'", key)
+            }
+        }
+    }
+}
+
+impl GenesisState {
+    /// Parses the genesis app state as sent by Tendermint in `InitChain`. A genesis file without
+    /// any app state leaves the chain without authorized monitors.
+    pub fn try_from_bytes(app_state: &[u8]) -> Result<GenesisState, GenesisError> {
+        if app_state.is_empty() {
+            return Ok(GenesisState::default());
+        }
+        let genesis: GenesisState =
+            serde_json::from_slice(app_state).map_err(GenesisError::Malformed)?;
+        for key in &genesis.authorized_monitors {
+            if identity::PublicKey::from_base58_string(key).is_err() {
+                return Err(GenesisError::MonitorPublicKey(key.clone()));
+            }
+        }
+        Ok(genesis)
+    }
+
+    /// State of the chain before its first block.
+    pub fn chain_state(self) -> ChainState {
+        ChainState {
+            authorized_monitors: self.authorized_monitors,
+            ..ChainState::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod parsing_genesis {
+    use super::*;
+
+    #[test]
+    fn authorized_monitors_are_taken_from_the_app_state() {
+        let key = identity::KeyPair::new().public_key().to_base58_string();
+        let app_state = format!(r#"{{"authorizedMonitors":["{}"]}}"#, key);
+
+        let chain_state = GenesisState::try_from_bytes(app_state.as_bytes())
+            .unwrap()
+            .chain_state();
+        assert_eq!(vec![key], chain_state.authorized_monitors);
+        assert_eq!(0, chain_state.height);
+    }
+
+    #[test]
+    fn missing_app_state_authorizes_nobody() {
+        let genesis = GenesisState::try_from_bytes(b"").unwrap();
+        assert!(genesis.authorized_monitors.is_empty());
+    }
+
+    #[test]
+    fn invalid_monitor_key_is_rejected() {
+        let app_state = br#"{"authorizedMonitors":["not-base58!"]}"#;
+        match GenesisState::try_from_bytes(app_state) {
+            Err(GenesisError::MonitorPublicKey(key)) => assert_eq!("not-base58!", key),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let app_state = br#"{"authorisedMonitors":[]}"#;
+        match GenesisState::try_from_bytes(app_state) {
+            Err(GenesisError::Malformed(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
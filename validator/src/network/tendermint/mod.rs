@@ -12,71 +12,549 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::network::authorization::AuthorizedMonitors;
+use crate::services::mixmining;
+use crate::services::mixmining::models::{ChainState, Measurement};
 use abci::*;
-use byteorder::{BigEndian, ByteOrder};
+use crypto::asymmetric::identity;
+use genesis::GenesisState;
+use log::{error, warn};
+use std::collections::BTreeMap;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use transaction::{MixminingTx, VerifiedTx};
 
-// Convert incoming tx network data to the proper BigEndian size. txs.len() > 8 will return 0
-fn convert_tx(tx: &[u8]) -> u64 {
-    if tx.len() < 8 {
-        let pad = 8 - tx.len();
-        let mut x = vec![0; pad];
-        x.extend_from_slice(tx);
-        return BigEndian::read_u64(x.as_slice());
-    }
-    BigEndian::read_u64(tx)
-}
+mod app_hash;
+mod genesis;
+mod transaction;
 
+/// ABCI application keeping the mixmining state in sync with the Tendermint chain.
+/// Mixmining transactions are validated in `check_tx` before they are allowed into the mempool.
+/// Once Tendermint delivers them as part of a block, they're collected and applied to the
+/// mixmining service all at once when the block is committed. Each monitor's transactions
+/// have to come with increasing nonces, so none of them can be applied twice. The resulting
+/// app hash is the Merkle root over the reputations of all measured mixnodes (see `app_hash`).
+///
+/// Only monitors authorized in the chain's genesis (see `genesis`) may submit transactions.
+/// The monitors authorized in the local configuration only apply to the REST API, as each
+/// validator deciding for itself would make them disagree about the blocks' outcome.
+///
+/// Reports submitted over the REST API would change those reputations behind the chain's back,
+/// so the API mustn't accept any while the ABCI application is running, and the application
+/// refuses to start on top of reputations recorded before the chain's first block.
 pub struct Abci {
-    count: u64,
+    address: SocketAddr,
+    mixmining_service: Arc<Mutex<mixmining::Service>>,
+    authorized_monitors: AuthorizedMonitors,
+    // measurements delivered as part of the current block, in the order they were delivered
+    block_measurements: Vec<(String, u64, Measurement)>,
+    // state of the chain including the transactions delivered as part of the current block
+    chain_state: ChainState,
+    // last nonce of each monitor out of the transactions let into the mempool since the last commit
+    mempool_nonces: BTreeMap<String, u64>,
 }
 
 impl Abci {
-    pub fn new() -> Abci {
-        Abci { count: 0 }
+    pub fn new(address: SocketAddr, mixmining_service: Arc<Mutex<mixmining::Service>>) -> Abci {
+        let chain_state = mixmining_service.lock().unwrap().chain_state();
+        Abci {
+            address,
+            mixmining_service,
+            authorized_monitors: authorized_monitors(&chain_state),
+            block_measurements: Vec::new(),
+            mempool_nonces: chain_state.nonces.clone(),
+            chain_state,
+        }
+    }
+
+    /// Run the ABCI server. This blocks the current thread for as long as the server is running.
+    /// `ready` is notified once the ABCI address turned out to be available. If it isn't,
+    /// for example because the port is already taken, the error is logged and `ready` is dropped.
+    pub fn run(self, ready: oneshot::Sender<()>) {
+        let address = self.address;
+        if let Err(err) = self.check_mixmining_state() {
+            error!("Refusing to start the Tendermint ABCI - {}", err);
+            return;
+        }
+        // `abci::run` binds the socket and serves forever without reporting back, so make sure
+        // the address can be bound at all before claiming that the server is starting
+        if let Err(err) = TcpListener::bind(address) {
+            error!(
+                "Failed to start the Tendermint ABCI on {} (is the port already in use?) - {}",
+                address, err
+            );
+            return;
+        }
+        println!("* starting Tendermint abci on {}", address);
+        let _ = ready.send(());
+        abci::run(address, self);
     }
 
-    pub async fn run(self) {
-        println!("* starting Tendermint abci");
-        abci::run_local(self);
+    // Before the first block, the mixmining database has to be empty. Otherwise reputations
+    // which never went through the chain, for example ones recorded from REST reports before
+    // the ABCI application was enabled, would end up in this validator's app hash only.
+    fn check_mixmining_state(&self) -> Result<(), &'static str> {
+        let mixmining_service = self.mixmining_service.lock().unwrap();
+        if self.chain_state.height == 0 && mixmining_service.last_measurement_recorded().is_some() {
+            return Err("the mixmining database contains measurements from outside of the chain");
+        }
+        Ok(())
     }
 }
 
+// Transactions are authorized against the monitors stored in the chain state, whose keys
+// were already checked when they were read from the genesis.
+fn authorized_monitors(chain_state: &ChainState) -> AuthorizedMonitors {
+    AuthorizedMonitors::new(
+        chain_state
+            .authorized_monitors
+            .iter()
+            .filter_map(|key| identity::PublicKey::from_base58_string(key).ok())
+            .collect(),
+    )
+}
+
 impl abci::Application for Abci {
-    // Validate transactions. Rule:  Transactions must be incremental: 1,2,3,4...
-    fn check_tx(&mut self, req: &RequestCheckTx) -> ResponseCheckTx {
-        // Get the Tx [u8] and convert to u64
-        let c = convert_tx(req.get_tx());
-        let mut response = ResponseCheckTx::new();
+    // Tell Tendermint which block we've got up to, so that after a restart only the blocks
+    // committed since then get replayed.
+    fn info(&mut self, _req: &RequestInfo) -> ResponseInfo {
+        let mut response = ResponseInfo::new();
+        response.set_last_block_height(self.chain_state.height);
+        response.set_last_block_app_hash(self.chain_state.app_hash.clone());
+        response
+    }
 
-        // Validation logic
-        if c != self.count + 1 {
-            response.set_code(1);
-            response.set_log(String::from("Count must be incremental!"));
-            return response;
+    // Called once, before the first block, with the app state of the genesis file.
+    fn init_chain(&mut self, req: &RequestInitChain) -> ResponseInitChain {
+        let chain_state = match GenesisState::try_from_bytes(req.get_app_state_bytes()) {
+            Ok(genesis) => genesis.chain_state(),
+            Err(err) => {
+                // every validator is given the same genesis, so none of them could go on either
+                error!(
+                    "Failed to start the chain - {}. The validator has to stop",
+                    err
+                );
+                std::process::exit(1);
+            }
+        };
+        let mut mixmining_service = self.mixmining_service.lock().unwrap();
+        if let Err(err) = mixmining_service.init_chain(chain_state) {
+            error!(
+                "Failed to record the genesis - {}. The validator has to stop",
+                err
+            );
+            std::process::exit(1);
         }
+        self.chain_state = mixmining_service.chain_state();
+        self.authorized_monitors = authorized_monitors(&self.chain_state);
+        self.mempool_nonces = self.chain_state.nonces.clone();
+        ResponseInitChain::new()
+    }
 
-        // Update state to keep state correct for next check_tx call
-        self.count = c;
+    // Validate transactions before they're put into the mempool.
+    fn check_tx(&mut self, req: &RequestCheckTx) -> ResponseCheckTx {
+        let mut response = ResponseCheckTx::new();
+        let tx = VerifiedTx::try_from_bytes(req.get_tx(), &self.authorized_monitors)
+            .and_then(|tx| tx.check_nonce(&self.mempool_nonces).map(|_| tx));
+        match tx {
+            Ok(tx) => {
+                self.mempool_nonces.insert(tx.monitor_key, tx.nonce);
+            }
+            Err(err) => {
+                response.set_code(err.code());
+                response.set_log(err.to_string());
+            }
+        }
         response
     }
 
     fn deliver_tx(&mut self, req: &RequestDeliverTx) -> ResponseDeliverTx {
-        // Get the Tx [u8]
-        let c = convert_tx(req.get_tx());
-        // Update state
-        self.count = c;
+        let mut response = ResponseDeliverTx::new();
+        let tx = VerifiedTx::try_from_bytes(req.get_tx(), &self.authorized_monitors)
+            .and_then(|tx| tx.check_nonce(&self.chain_state.nonces).map(|_| tx));
+        let tx = match tx {
+            Ok(tx) => tx,
+            Err(err) => {
+                warn!("Refusing to apply invalid transaction - {}", err);
+                response.set_code(err.code());
+                response.set_log(err.to_string());
+                return response;
+            }
+        };
+
+        match tx.tx {
+            MixminingTx::Measurement(measurement) => self.block_measurements.push((
+                measurement.pub_key.clone(),
+                measurement.layer,
+                measurement.measurement(),
+            )),
+        }
+        self.chain_state.nonces.insert(tx.monitor_key, tx.nonce);
+
         // Return default code 0 == bueno
-        ResponseDeliverTx::new()
+        response
     }
 
     fn commit(&mut self, _req: &RequestCommit) -> ResponseCommit {
-        // Create the response
+        let measurements = std::mem::take(&mut self.block_measurements);
+        let height = self.chain_state.height + 1;
+        let nonces = self.chain_state.nonces.clone();
+        let authorized_monitors = self.chain_state.authorized_monitors.clone();
+        let mut mixmining_service = self.mixmining_service.lock().unwrap();
+        let committed = mixmining_service.commit_block(measurements, |reputations| ChainState {
+            height,
+            app_hash: app_hash::app_hash(reputations),
+            nonces,
+            authorized_monitors,
+        });
+        if let Err(err) = committed {
+            // Tendermint considers the block committed no matter what, so carrying on would leave
+            // this validator out of sync with the chain for good. Stop instead, the block is
+            // replayed on restart as the last persisted height doesn't include it.
+            error!(
+                "Failed to record the committed block {} - {}. The validator has to stop",
+                height, err
+            );
+            std::process::exit(1);
+        }
+        self.chain_state = mixmining_service.chain_state();
+        // whatever is still in the mempool gets checked again against the new state
+        self.mempool_nonces = self.chain_state.nonces.clone();
+
         let mut response = ResponseCommit::new();
-        // Convert count to bits
-        let mut buf = [0; 8];
-        BigEndian::write_u64(&mut buf, self.count);
-        // Set data so last state is included in the block
-        response.set_data(buf.to_vec());
+        // Set data so the resulting state is included in the next block
+        response.set_data(self.chain_state.app_hash.clone());
         response
     }
 }
+
+#[cfg(test)]
+mod mixmining_transactions {
+    use super::*;
+    use crate::services::mixmining::db::MixminingDb;
+    use crate::services::mixmining::models::Report;
+    use crate::services::mixmining::tests::{fake_node_test_result, MONITOR_KEY};
+    use transaction::{
+        MeasurementTx, MonitorTx, CODE_INVALID_MEASUREMENT, CODE_MALFORMED_TX, CODE_STALE_NONCE,
+        CODE_UNAUTHORIZED,
+    };
+
+    const CODE_OK: u32 = 0;
+
+    struct Fixture {
+        abci: Abci,
+        service: Arc<Mutex<mixmining::Service>>,
+        monitor: identity::KeyPair,
+        node_key: String,
+        nonce: u64,
+    }
+
+    impl Fixture {
+        fn new() -> Fixture {
+            let service = Arc::new(Mutex::new(mixmining::Service::new(MixminingDb::new())));
+            let address = "127.0.0.1:26658".parse().unwrap();
+            let monitor = identity::KeyPair::new();
+            let mut abci = Abci::new(address, Arc::clone(&service));
+            init_chain(&mut abci, &monitor);
+            Fixture {
+                abci,
+                service,
+                monitor,
+                node_key: identity::KeyPair::new().public_key().to_base58_string(),
+                nonce: 0,
+            }
+        }
+
+        // each transaction gets the next nonce of the monitor
+        fn measurement_tx(&mut self, packets_received: u64) -> Vec<u8> {
+            self.nonce += 1;
+            MonitorTx {
+                nonce: self.nonce,
+                tx: MixminingTx::Measurement(MeasurementTx {
+                    pub_key: self.node_key.clone(),
+                    layer: 1,
+                    timestamp: 1234,
+                    packets_sent: 10,
+                    packets_received,
+                }),
+            }
+            .signed_by(&self.monitor)
+        }
+    }
+
+    fn init_chain(abci: &mut Abci, monitor: &identity::KeyPair) {
+        let app_state = format!(
+            r#"{{"authorizedMonitors":["{}"]}}"#,
+            monitor.public_key().to_base58_string()
+        );
+        let mut req = RequestInitChain::new();
+        req.set_app_state_bytes(app_state.into_bytes());
+        abci.init_chain(&req);
+    }
+
+    fn check_tx(abci: &mut Abci, tx: Vec<u8>) -> ResponseCheckTx {
+        let mut req = RequestCheckTx::new();
+        req.set_tx(tx);
+        abci.check_tx(&req)
+    }
+
+    fn deliver_tx(abci: &mut Abci, tx: Vec<u8>) -> ResponseDeliverTx {
+        let mut req = RequestDeliverTx::new();
+        req.set_tx(tx);
+        abci.deliver_tx(&req)
+    }
+
+    fn commit(abci: &mut Abci) -> Vec<u8> {
+        abci.commit(&RequestCommit::new()).get_data().to_vec()
+    }
+
+    #[test]
+    fn check_tx_accepts_valid_measurement() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(CODE_OK, check_tx(&mut fixture.abci, tx).get_code());
+    }
+
+    #[test]
+    fn check_tx_rejects_malformed_and_invalid_transactions() {
+        let mut fixture = Fixture::new();
+        assert_eq!(
+            CODE_MALFORMED_TX,
+            check_tx(&mut fixture.abci, b"foomp".to_vec()).get_code()
+        );
+        let tx = fixture.measurement_tx(11);
+        assert_eq!(
+            CODE_INVALID_MEASUREMENT,
+            check_tx(&mut fixture.abci, tx).get_code()
+        );
+    }
+
+    #[test]
+    fn check_tx_rejects_transactions_from_unknown_monitors() {
+        let mut fixture = Fixture::new();
+        fixture.monitor = identity::KeyPair::new();
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(
+            CODE_UNAUTHORIZED,
+            check_tx(&mut fixture.abci, tx).get_code()
+        );
+    }
+
+    #[test]
+    fn check_tx_does_not_modify_mixmining_state() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        check_tx(&mut fixture.abci, tx);
+        assert!(fixture.service.lock().unwrap().reputations(None).is_empty());
+    }
+
+    #[test]
+    fn committing_block_records_delivered_measurements() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(CODE_OK, deliver_tx(&mut fixture.abci, tx).get_code());
+        assert!(fixture.service.lock().unwrap().reputations(None).is_empty());
+
+        commit(&mut fixture.abci);
+        let reputations = fixture.service.lock().unwrap().reputations(None);
+        assert_eq!(1, reputations.len());
+        assert_eq!(fixture.node_key, reputations[0].public_key);
+        assert_eq!(9, reputations[0].latest.packets_received);
+    }
+
+    #[test]
+    fn measurements_delivered_in_one_block_are_applied_in_order() {
+        let mut fixture = Fixture::new();
+        for packets_received in &[9, 3] {
+            let tx = fixture.measurement_tx(*packets_received);
+            deliver_tx(&mut fixture.abci, tx);
+        }
+        commit(&mut fixture.abci);
+
+        let reputations = fixture.service.lock().unwrap().reputations(None);
+        assert_eq!(3, reputations[0].latest.packets_received);
+        assert_eq!(2, reputations[0].recent.len());
+    }
+
+    #[test]
+    fn invalid_measurement_is_not_recorded() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(11);
+        assert_eq!(
+            CODE_INVALID_MEASUREMENT,
+            deliver_tx(&mut fixture.abci, tx).get_code()
+        );
+        commit(&mut fixture.abci);
+        assert!(fixture.service.lock().unwrap().reputations(None).is_empty());
+    }
+
+    #[test]
+    fn app_hash_only_depends_on_delivered_transactions() {
+        let mut first = Fixture::new();
+        let mut second = Fixture::new();
+        second.node_key = first.node_key.clone();
+        assert!(commit(&mut first.abci).is_empty());
+
+        let tx = first.measurement_tx(9);
+        deliver_tx(&mut first.abci, tx);
+        let tx = second.measurement_tx(9);
+        deliver_tx(&mut second.abci, tx);
+        let app_hash = commit(&mut first.abci);
+        assert!(!app_hash.is_empty());
+        assert_eq!(app_hash, commit(&mut second.abci));
+
+        let tx = first.measurement_tx(8);
+        deliver_tx(&mut first.abci, tx);
+        assert_ne!(app_hash, commit(&mut first.abci));
+    }
+
+    #[test]
+    fn info_reports_the_last_committed_block() {
+        let mut fixture = Fixture::new();
+        let info = fixture.abci.info(&RequestInfo::new());
+        assert_eq!(0, info.get_last_block_height());
+        assert!(info.get_last_block_app_hash().is_empty());
+
+        let tx = fixture.measurement_tx(9);
+        deliver_tx(&mut fixture.abci, tx);
+        commit(&mut fixture.abci);
+        let app_hash = commit(&mut fixture.abci);
+
+        let info = fixture.abci.info(&RequestInfo::new());
+        assert_eq!(2, info.get_last_block_height());
+        assert_eq!(app_hash, info.get_last_block_app_hash());
+    }
+
+    #[test]
+    fn restarted_abci_continues_from_the_last_committed_block() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        deliver_tx(&mut fixture.abci, tx);
+        let app_hash = commit(&mut fixture.abci);
+
+        let address = "127.0.0.1:26658".parse().unwrap();
+        let mut abci = Abci::new(address, Arc::clone(&fixture.service));
+        let info = abci.info(&RequestInfo::new());
+        assert_eq!(1, info.get_last_block_height());
+        assert_eq!(app_hash, info.get_last_block_app_hash());
+    }
+
+    #[test]
+    fn check_tx_rejects_replayed_transactions() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(CODE_OK, check_tx(&mut fixture.abci, tx.clone()).get_code());
+        assert_eq!(
+            CODE_STALE_NONCE,
+            check_tx(&mut fixture.abci, tx.clone()).get_code()
+        );
+
+        deliver_tx(&mut fixture.abci, tx.clone());
+        commit(&mut fixture.abci);
+        assert_eq!(CODE_STALE_NONCE, check_tx(&mut fixture.abci, tx).get_code());
+    }
+
+    #[test]
+    fn check_tx_lets_rejected_nonces_be_reused_after_commit() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(CODE_OK, check_tx(&mut fixture.abci, tx.clone()).get_code());
+
+        // the transaction never made it into a block, so it's still valid after the commit
+        commit(&mut fixture.abci);
+        assert_eq!(CODE_OK, check_tx(&mut fixture.abci, tx).get_code());
+    }
+
+    #[test]
+    fn replayed_transaction_is_not_recorded() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(
+            CODE_OK,
+            deliver_tx(&mut fixture.abci, tx.clone()).get_code()
+        );
+        assert_eq!(
+            CODE_STALE_NONCE,
+            deliver_tx(&mut fixture.abci, tx.clone()).get_code()
+        );
+        commit(&mut fixture.abci);
+        assert_eq!(
+            CODE_STALE_NONCE,
+            deliver_tx(&mut fixture.abci, tx).get_code()
+        );
+        commit(&mut fixture.abci);
+
+        let reputations = fixture.service.lock().unwrap().reputations(None);
+        assert_eq!(1, reputations[0].recent.len());
+    }
+
+    #[test]
+    fn committed_nonces_are_picked_up_by_a_new_abci() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.measurement_tx(9);
+        deliver_tx(&mut fixture.abci, tx.clone());
+        commit(&mut fixture.abci);
+
+        let address = "127.0.0.1:26658".parse().unwrap();
+        let mut abci = Abci::new(address, Arc::clone(&fixture.service));
+        assert_eq!(CODE_STALE_NONCE, check_tx(&mut abci, tx).get_code());
+    }
+
+    #[test]
+    fn monitors_authorized_in_the_genesis_are_picked_up_by_a_new_abci() {
+        let mut fixture = Fixture::new();
+        let address = "127.0.0.1:26658".parse().unwrap();
+        fixture.abci = Abci::new(address, Arc::clone(&fixture.service));
+
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(CODE_OK, deliver_tx(&mut fixture.abci, tx).get_code());
+        commit(&mut fixture.abci);
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(CODE_OK, check_tx(&mut fixture.abci, tx).get_code());
+    }
+
+    #[test]
+    fn deliver_tx_rejects_transactions_without_a_genesis() {
+        let service = Arc::new(Mutex::new(mixmining::Service::new(MixminingDb::new())));
+        let address = "127.0.0.1:26658".parse().unwrap();
+        let mut fixture = Fixture::new();
+        fixture.abci = Abci::new(address, service);
+
+        let tx = fixture.measurement_tx(9);
+        assert_eq!(
+            CODE_UNAUTHORIZED,
+            deliver_tx(&mut fixture.abci, tx).get_code()
+        );
+    }
+
+    #[test]
+    fn abci_does_not_start_on_measurements_recorded_outside_the_chain() {
+        let mut db = MixminingDb::new();
+        db.apply_report(
+            MONITOR_KEY,
+            Report {
+                timestamp: 1234,
+                results: vec![fake_node_test_result("abc", 1, 9)],
+            },
+        )
+        .unwrap();
+        let service = Arc::new(Mutex::new(mixmining::Service::new(db)));
+        let address = "127.0.0.1:26658".parse().unwrap();
+
+        assert!(Abci::new(address, service).check_mixmining_state().is_err());
+    }
+
+    #[test]
+    fn abci_starts_on_measurements_recorded_through_the_chain() {
+        let mut fixture = Fixture::new();
+        assert!(fixture.abci.check_mixmining_state().is_ok());
+
+        let tx = fixture.measurement_tx(9);
+        deliver_tx(&mut fixture.abci, tx);
+        commit(&mut fixture.abci);
+        let address = "127.0.0.1:26658".parse().unwrap();
+        let abci = Abci::new(address, Arc::clone(&fixture.service));
+        assert!(abci.check_mixmining_state().is_ok());
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2020 Nym Technologies SA
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::network::authorization::{AuthorizationError, AuthorizedMonitors};
+use crate::services::mixmining::models::{InvalidMeasurement, Measurement, NodeTestResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// ABCI response codes for rejected transactions. 0 (the default) means the transaction is valid.
+pub(crate) const CODE_MALFORMED_TX: u32 = 1;
+pub(crate) const CODE_INVALID_MEASUREMENT: u32 = 2;
+pub(crate) const CODE_INVALID_NODE_KEY: u32 = 3;
+pub(crate) const CODE_UNAUTHORIZED: u32 = 4;
+pub(crate) const CODE_STALE_NONCE: u32 = 5;
+
+#[derive(Debug)]
+pub enum TransactionError {
+    Malformed(serde_json::Error),
+    InvalidMeasurement(String),
+    InvalidNodeKey(String),
+    Unauthorized(AuthorizationError),
+    StaleNonce(u64),
+}
+
+impl TransactionError {
+    pub fn code(&self) -> u32 {
+        match self {
+            TransactionError::Malformed(_) => CODE_MALFORMED_TX,
+            TransactionError::InvalidMeasurement(_) => CODE_INVALID_MEASUREMENT,
+            TransactionError::InvalidNodeKey(_) => CODE_INVALID_NODE_KEY,
+            TransactionError::Unauthorized(_) => CODE_UNAUTHORIZED,
+            TransactionError::StaleNonce(_) => CODE_STALE_NONCE,
+        }
+    }
+}
+
+impl From<InvalidMeasurement> for TransactionError {
+    fn from(err: InvalidMeasurement) -> Self {
+        match err {
+            InvalidMeasurement::NodeKey(reason) => TransactionError::InvalidNodeKey(reason),
+            InvalidMeasurement::Packets(reason) => TransactionError::InvalidMeasurement(reason),
+        }
+    }
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::Malformed(err) => write!(f, "malformed transaction - {}", err),
+            TransactionError::InvalidMeasurement(reason) => {
+                write!(f, "invalid measurement - {}", reason)
+            }
+            TransactionError::InvalidNodeKey(reason) => {
+                write!(f, "invalid node public key - {}", reason)
+            }
+            TransactionError::Unauthorized(err) => write!(f, "unauthorized transaction - {}", err),
+            TransactionError::StaleNonce(last) => write!(
+                f,
+                "nonce has to be greater than {}, the last one accepted from this monitor",
+                last
+            ),
+        }
+    }
+}
+
+/// Reliability measurement of a single mixnode, as observed by a network monitor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct MeasurementTx {
+    pub pub_key: String,
+    pub layer: u64,
+    pub timestamp: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+impl MeasurementTx {
+    // the same rules apply as to the results of reports submitted over the REST API
+    fn validate(&self) -> Result<(), TransactionError> {
+        let result = NodeTestResult {
+            public_key: self.pub_key.clone(),
+            layer: self.layer,
+            packets_sent: self.packets_sent,
+            packets_received: self.packets_received,
+        };
+        result.validate().map_err(Into::into)
+    }
+
+    pub fn measurement(&self) -> Measurement {
+        Measurement {
+            timestamp: self.timestamp,
+            packets_sent: self.packets_sent,
+            packets_received: self.packets_received,
+        }
+    }
+}
+
+/// All transactions the validator's ABCI application knows how to handle.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MixminingTx {
+    Measurement(MeasurementTx),
+}
+
+impl MixminingTx {
+    fn validate(&self) -> Result<(), TransactionError> {
+        match self {
+            MixminingTx::Measurement(measurement) => measurement.validate(),
+        }
+    }
+}
+
+/// A transaction as signed by the monitor submitting it. Each transaction of a monitor
+/// has to carry a greater nonce than the ones before it, so that none of them can be
+/// applied more than once.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct MonitorTx {
+    pub nonce: u64,
+    pub tx: MixminingTx,
+}
+
+impl MonitorTx {
+    #[cfg(test)]
+    pub fn signed_by(&self, keypair: &crypto::asymmetric::identity::KeyPair) -> Vec<u8> {
+        let tx = serde_json::to_string(self).unwrap();
+        let signature = keypair.private_key().sign(tx.as_bytes());
+        serde_json::to_vec(&SignedTx {
+            monitor_key: keypair.public_key().to_base58_string(),
+            signature: signature.to_base58_string(),
+            tx,
+        })
+        .unwrap()
+    }
+}
+
+/// What actually goes on the wire: the JSON-encoded `MonitorTx` as a string, along with
+/// the base58-encoded identity key of the monitor that submitted it and its signature of
+/// exactly that string.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct SignedTx {
+    monitor_key: String,
+    signature: String,
+    tx: String,
+}
+
+/// A well-formed transaction signed by one of the authorized monitors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedTx {
+    pub monitor_key: String,
+    pub nonce: u64,
+    pub tx: MixminingTx,
+}
+
+impl VerifiedTx {
+    /// Decodes the raw transaction and makes sure it's well-formed and signed by one
+    /// of the authorized monitors.
+    pub fn try_from_bytes(
+        bytes: &[u8],
+        authorized_monitors: &AuthorizedMonitors,
+    ) -> Result<Self, TransactionError> {
+        let signed: SignedTx =
+            serde_json::from_slice(bytes).map_err(TransactionError::Malformed)?;
+        // the signature covers the transaction exactly as it was sent, so it has to be checked
+        // before the transaction gets parsed
+        authorized_monitors
+            .verify(&signed.monitor_key, &signed.signature, signed.tx.as_bytes())
+            .map_err(TransactionError::Unauthorized)?;
+        let monitor_tx: MonitorTx =
+            serde_json::from_str(&signed.tx).map_err(TransactionError::Malformed)?;
+        monitor_tx.tx.validate()?;
+        Ok(VerifiedTx {
+            monitor_key: signed.monitor_key,
+            nonce: monitor_tx.nonce,
+            tx: monitor_tx.tx,
+        })
+    }
+
+    /// Makes sure the nonce is greater than the last one accepted from the same monitor,
+    /// given the last accepted nonces of all monitors.
+    pub fn check_nonce(&self, nonces: &BTreeMap<String, u64>) -> Result<(), TransactionError> {
+        match nonces.get(&self.monitor_key) {
+            Some(&last) if self.nonce <= last => Err(TransactionError::StaleNonce(last)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod decoding_transactions {
+    use super::*;
+    use crypto::asymmetric::identity;
+
+    fn node_key() -> String {
+        identity::KeyPair::new().public_key().to_base58_string()
+    }
+
+    fn measurement_tx(pub_key: String, packets_sent: u64, packets_received: u64) -> MixminingTx {
+        MixminingTx::Measurement(MeasurementTx {
+            pub_key,
+            layer: 1,
+            timestamp: 1234,
+            packets_sent,
+            packets_received,
+        })
+    }
+
+    fn monitor() -> (identity::KeyPair, AuthorizedMonitors) {
+        let keypair = identity::KeyPair::new();
+        let monitors = AuthorizedMonitors::new(vec![*keypair.public_key()]);
+        (keypair, monitors)
+    }
+
+    fn signed_by(tx: &MixminingTx, keypair: &identity::KeyPair) -> Vec<u8> {
+        MonitorTx {
+            nonce: 1,
+            tx: tx.clone(),
+        }
+        .signed_by(keypair)
+    }
+
+    // signs whatever is given as the transaction, well-formed or not
+    fn sign_raw(tx: &str, keypair: &identity::KeyPair) -> Vec<u8> {
+        serde_json::to_vec(&SignedTx {
+            monitor_key: keypair.public_key().to_base58_string(),
+            signature: keypair.private_key().sign(tx.as_bytes()).to_base58_string(),
+            tx: tx.to_owned(),
+        })
+        .unwrap()
+    }
+
+    fn decode(tx: &MixminingTx) -> Result<MixminingTx, TransactionError> {
+        let (keypair, monitors) = monitor();
+        VerifiedTx::try_from_bytes(&signed_by(tx, &keypair), &monitors).map(|verified| verified.tx)
+    }
+
+    #[test]
+    fn valid_measurement_can_be_decoded() {
+        let (keypair, monitors) = monitor();
+        let tx = measurement_tx(node_key(), 10, 9);
+        let verified = VerifiedTx::try_from_bytes(&signed_by(&tx, &keypair), &monitors).unwrap();
+
+        assert_eq!(tx, verified.tx);
+        assert_eq!(1, verified.nonce);
+        assert_eq!(
+            keypair.public_key().to_base58_string(),
+            verified.monitor_key
+        );
+    }
+
+    #[test]
+    fn garbage_is_rejected_as_malformed() {
+        let (_, monitors) = monitor();
+        let err = VerifiedTx::try_from_bytes(b"foomp", &monitors).unwrap_err();
+        assert_eq!(CODE_MALFORMED_TX, err.code());
+    }
+
+    #[test]
+    fn unknown_transaction_type_is_rejected_as_malformed() {
+        let (keypair, monitors) = monitor();
+        let bytes = sign_raw(r#"{"nonce":1,"tx":{"foomp":{}}}"#, &keypair);
+        let err = VerifiedTx::try_from_bytes(&bytes, &monitors).unwrap_err();
+        assert_eq!(CODE_MALFORMED_TX, err.code());
+    }
+
+    #[test]
+    fn unsigned_transaction_is_rejected_as_malformed() {
+        let (_, monitors) = monitor();
+        let tx = MonitorTx {
+            nonce: 1,
+            tx: measurement_tx(node_key(), 10, 9),
+        };
+        let bytes = serde_json::to_vec(&tx).unwrap();
+        let err = VerifiedTx::try_from_bytes(&bytes, &monitors).unwrap_err();
+        assert_eq!(CODE_MALFORMED_TX, err.code());
+    }
+
+    #[test]
+    fn transaction_with_unknown_fields_is_rejected_as_malformed() {
+        let (keypair, monitors) = monitor();
+        let bytes = signed_by(&measurement_tx(node_key(), 10, 9), &keypair);
+        let mut envelope: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        envelope["foomp"] = serde_json::Value::Bool(true);
+        let bytes = serde_json::to_vec(&envelope).unwrap();
+        let err = VerifiedTx::try_from_bytes(&bytes, &monitors).unwrap_err();
+        assert_eq!(CODE_MALFORMED_TX, err.code());
+
+        let tx = format!(
+            r#"{{"nonce":1,"tx":{{"measurement":{{"pubKey":"{}","layer":1,"timestamp":1234,"packetsSent":10,"packetsReceived":9,"foomp":true}}}}}}"#,
+            node_key()
+        );
+        let err = VerifiedTx::try_from_bytes(&sign_raw(&tx, &keypair), &monitors).unwrap_err();
+        assert_eq!(CODE_MALFORMED_TX, err.code());
+    }
+
+    #[test]
+    fn measurement_with_more_received_than_sent_packets_is_rejected() {
+        let err = decode(&measurement_tx(node_key(), 10, 11)).unwrap_err();
+        assert_eq!(CODE_INVALID_MEASUREMENT, err.code());
+    }
+
+    #[test]
+    fn measurement_without_sent_packets_is_rejected() {
+        let err = decode(&measurement_tx(node_key(), 0, 0)).unwrap_err();
+        assert_eq!(CODE_INVALID_MEASUREMENT, err.code());
+    }
+
+    #[test]
+    fn measurement_without_public_key_is_rejected() {
+        let err = decode(&measurement_tx("".to_owned(), 10, 10)).unwrap_err();
+        assert_eq!(CODE_INVALID_NODE_KEY, err.code());
+    }
+
+    #[test]
+    fn measurement_with_wrong_length_public_key_is_rejected() {
+        let short_key = bs58::encode([1u8; 31]).into_string();
+        let err = decode(&measurement_tx(short_key, 10, 10)).unwrap_err();
+        assert_eq!(CODE_INVALID_NODE_KEY, err.code());
+
+        let err = decode(&measurement_tx("not-base58!".to_owned(), 10, 10)).unwrap_err();
+        assert_eq!(CODE_INVALID_NODE_KEY, err.code());
+    }
+
+    #[test]
+    fn transaction_signed_by_unknown_monitor_is_rejected() {
+        let (_, monitors) = monitor();
+        let bytes = signed_by(
+            &measurement_tx(node_key(), 10, 9),
+            &identity::KeyPair::new(),
+        );
+        let err = VerifiedTx::try_from_bytes(&bytes, &monitors).unwrap_err();
+        assert_eq!(CODE_UNAUTHORIZED, err.code());
+    }
+
+    #[test]
+    fn tampered_transaction_is_rejected() {
+        let (keypair, monitors) = monitor();
+        let bytes = signed_by(&measurement_tx(node_key(), 10, 9), &keypair);
+        let tampered = String::from_utf8(bytes)
+            .unwrap()
+            .replace(r#"\"packetsReceived\":9"#, r#"\"packetsReceived\":10"#);
+        let err = VerifiedTx::try_from_bytes(tampered.as_bytes(), &monitors).unwrap_err();
+        assert_eq!(CODE_UNAUTHORIZED, err.code());
+    }
+
+    #[test]
+    fn nonce_has_to_be_greater_than_the_last_one_of_the_same_monitor() {
+        let (keypair, monitors) = monitor();
+        let bytes = signed_by(&measurement_tx(node_key(), 10, 9), &keypair);
+        let verified = VerifiedTx::try_from_bytes(&bytes, &monitors).unwrap();
+        let mut nonces = BTreeMap::new();
+        assert!(verified.check_nonce(&nonces).is_ok());
+
+        nonces.insert("someone else".to_owned(), 5);
+        assert!(verified.check_nonce(&nonces).is_ok());
+
+        nonces.insert(verified.monitor_key.clone(), 0);
+        assert!(verified.check_nonce(&nonces).is_ok());
+
+        nonces.insert(verified.monitor_key.clone(), 1);
+        let err = verified.check_nonce(&nonces).unwrap_err();
+        assert_eq!(CODE_STALE_NONCE, err.code());
+    }
+}
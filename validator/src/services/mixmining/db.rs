@@ -1,37 +1,514 @@
-use super::Mixnode;
+use super::{ChainState, InvalidMeasurement, Measurement, Mixnode, Report, Reputation};
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::Transactional;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
 
-/// A (currently RAM-based) data store to keep tabs on which nodes have what
-/// stake assigned to them.
-#[derive(Clone, Debug, PartialEq)]
+const DEFAULT_CAPACITY: usize = 6;
+
+/// Number of most recent measurements kept for each mixnode, unless configured otherwise.
+pub const DEFAULT_HISTORY_DEPTH: usize = 10;
+
+/// Weight of the newest measurement in the smoothed reliability, unless configured otherwise.
+pub const DEFAULT_SMOOTHING_FACTOR: f64 = 0.3;
+
+/// How long (in milliseconds) a mixnode can go unmeasured before its smoothed reliability
+/// is started over rather than updated, unless configured otherwise.
+pub const DEFAULT_SMOOTHING_RESET_AFTER: u64 = 24 * 60 * 60 * 1000;
+
+// Version of the records written to the on-disk store. New fields added to the stored types
+// should be `#[serde(default)]` so that records written by older validators can still be read.
+// Anything more invasive than that requires bumping this version.
+const RECORD_VERSION: u8 = 1;
+
+const MIXNODES_TREE: &str = "mixnodes";
+const REPUTATIONS_TREE: &str = "reputations";
+const METADATA_TREE: &str = "metadata";
+const MONITORS_TREE: &str = "monitors";
+const CAPACITY_KEY: &str = "capacity";
+const CHAIN_STATE_KEY: &str = "chain_state";
+
+#[derive(Debug)]
+pub enum MixminingDbError {
+    StoreError(sled::Error),
+    MalformedRecord(serde_json::Error),
+    UnsupportedRecordVersion(u8),
+    StaleReport(u64),
+    InvalidResult(String, InvalidMeasurement),
+}
+
+impl fmt::Display for MixminingDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MixminingDbError::StoreError(err) => write!(f, "on-disk store failure - {}", err),
+            MixminingDbError::MalformedRecord(err) => write!(f, "malformed record - {}", err),
+            MixminingDbError::UnsupportedRecordVersion(version) => {
+                write!(f, "unsupported record version {}", version)
+            }
+            MixminingDbError::StaleReport(last_accepted) => write!(
+                f,
+                "report isn't newer than the last one accepted from its monitor (at {})",
+                last_accepted
+            ),
+            MixminingDbError::InvalidResult(public_key, err) => {
+                write!(f, "invalid result for mixnode '{}' - {}", public_key, err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MixminingDbError {}
+
+impl From<sled::Error> for MixminingDbError {
+    fn from(err: sled::Error) -> Self {
+        MixminingDbError::StoreError(err)
+    }
+}
+
+impl From<serde_json::Error> for MixminingDbError {
+    fn from(err: serde_json::Error) -> Self {
+        MixminingDbError::MalformedRecord(err)
+    }
+}
+
+#[derive(Serialize)]
+struct VersionedRecord<'a, T> {
+    version: u8,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct RecordVersion {
+    version: u8,
+}
+
+#[derive(Deserialize)]
+struct RecordData<T> {
+    data: T,
+}
+
+fn encode_record<T: Serialize>(data: &T) -> Vec<u8> {
+    let record = VersionedRecord {
+        version: RECORD_VERSION,
+        data,
+    };
+    serde_json::to_vec(&record).expect("mixmining records are always serializable")
+}
+
+fn decode_record<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, MixminingDbError> {
+    // check the version before trying to interpret the data, as its layout depends on it
+    let RecordVersion { version } = serde_json::from_slice(bytes)?;
+    if version > RECORD_VERSION {
+        return Err(MixminingDbError::UnsupportedRecordVersion(version));
+    }
+    let RecordData { data } = serde_json::from_slice(bytes)?;
+    Ok(data)
+}
+
+/// A data store to keep tabs on which nodes have what stake assigned to them.
+/// Everything is kept in RAM and, if the database was created with `MixminingDb::open`,
+/// all changes are additionally written through to the on-disk store. Everything a monitor
+/// report or a committed block changes is written in a single transaction, so it's either
+/// recorded as a whole or not at all.
+#[derive(Clone, Debug)]
 pub struct MixminingDb {
-    mixnodes: Vec<Mixnode>,
+    // announced mixnodes, by their public keys
+    mixnodes: BTreeMap<String, Mixnode>,
     capacity: usize,
+    reputations: BTreeMap<String, Reputation>,
+    // timestamp of the last report accepted from each monitor, by its public key
+    monitor_reports: BTreeMap<String, u64>,
+    chain_state: ChainState,
+    history_depth: usize,
+    smoothing_factor: f64,
+    smoothing_reset_after: u64,
+    store: Option<sled::Db>,
+}
+
+// Two databases are equal if they hold the same data, regardless of whether it's persisted.
+impl PartialEq for MixminingDb {
+    fn eq(&self, other: &Self) -> bool {
+        self.mixnodes == other.mixnodes
+            && self.capacity == other.capacity
+            && self.reputations == other.reputations
+            && self.monitor_reports == other.monitor_reports
+            && self.chain_state == other.chain_state
+    }
 }
 
 impl MixminingDb {
+    /// Creates a purely in-memory database. Its contents are lost once it's dropped.
     pub fn new() -> MixminingDb {
-        let mixnodes = Vec::<Mixnode>::new();
         MixminingDb {
-            capacity: 6,
-            mixnodes,
+            capacity: DEFAULT_CAPACITY,
+            mixnodes: BTreeMap::new(),
+            reputations: BTreeMap::new(),
+            monitor_reports: BTreeMap::new(),
+            chain_state: ChainState::default(),
+            history_depth: DEFAULT_HISTORY_DEPTH,
+            smoothing_factor: DEFAULT_SMOOTHING_FACTOR,
+            smoothing_reset_after: DEFAULT_SMOOTHING_RESET_AFTER,
+            store: None,
         }
     }
 
-    pub fn add(&mut self, mixnode: Mixnode) {
-        self.mixnodes.push(mixnode);
+    /// Sets how many of the most recent measurements are kept for each mixnode (at least one).
+    /// Histories loaded from disk which are longer than that are trimmed on their next update.
+    pub fn with_history_depth(mut self, history_depth: usize) -> Self {
+        self.history_depth = std::cmp::max(history_depth, 1);
+        self
     }
 
-    pub fn get_mixnodes(&self) -> &Vec<Mixnode> {
-        &self.mixnodes
+    pub fn history_depth(&self) -> usize {
+        self.history_depth
     }
 
-    pub fn set_capacity(&mut self, capacity: usize) {
+    /// Sets how the smoothed reliability of mixnodes is maintained. `factor` (between 0 and 1)
+    /// is the weight of each new measurement, while `reset_after` is how long, in milliseconds,
+    /// a mixnode can go unmeasured before its smoothed reliability is started over from scratch.
+    pub fn with_reliability_smoothing(mut self, factor: f64, reset_after: u64) -> Self {
+        self.smoothing_factor = factor.clamp(0.0, 1.0);
+        self.smoothing_reset_after = reset_after;
+        self
+    }
+
+    /// Opens (or creates) the on-disk database at the given path and loads all existing records.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MixminingDb, MixminingDbError> {
+        let store = sled::open(path)?;
+        let mut db = MixminingDb::new();
+
+        for entry in store.open_tree(MIXNODES_TREE)?.iter() {
+            let (_, value) = entry?;
+            let mixnode: Mixnode = decode_record(&value)?;
+            db.mixnodes.insert(mixnode.public_key.clone(), mixnode);
+        }
+
+        for entry in store.open_tree(REPUTATIONS_TREE)?.iter() {
+            let (_, value) = entry?;
+            let reputation: Reputation = decode_record(&value)?;
+            db.reputations
+                .insert(reputation.public_key.clone(), reputation);
+        }
+
+        for entry in store.open_tree(MONITORS_TREE)?.iter() {
+            let (key, value) = entry?;
+            let monitor = String::from_utf8_lossy(&key).into_owned();
+            db.monitor_reports.insert(monitor, decode_record(&value)?);
+        }
+
+        let metadata = store.open_tree(METADATA_TREE)?;
+        if let Some(capacity) = metadata.get(CAPACITY_KEY)? {
+            db.capacity = decode_record(&capacity)?;
+        }
+        if let Some(chain_state) = metadata.get(CHAIN_STATE_KEY)? {
+            db.chain_state = decode_record(&chain_state)?;
+        }
+
+        db.store = Some(store);
+        Ok(db)
+    }
+
+    // Writes the record through to the on-disk store (if there is one). Callers only update
+    // the in-memory state once this succeeded, so that it never holds anything the store doesn't.
+    // Like any other write, the record is flushed to disk by sled in the background and when
+    // the validator shuts down.
+    fn persist<K: AsRef<[u8]>, T: Serialize>(
+        &self,
+        tree_name: &str,
+        key: K,
+        value: &T,
+    ) -> Result<(), MixminingDbError> {
+        if let Some(store) = &self.store {
+            store
+                .open_tree(tree_name)?
+                .insert(key, encode_record(value))?;
+        }
+        Ok(())
+    }
+
+    /// Checks whether the on-disk store (if there is one) can still be read from.
+    pub fn is_reachable(&self) -> bool {
+        match &self.store {
+            Some(store) => store
+                .open_tree(METADATA_TREE)
+                .and_then(|tree| tree.get(CAPACITY_KEY))
+                .is_ok(),
+            None => true,
+        }
+    }
+
+    /// Makes sure everything written so far has made it to disk.
+    pub fn flush(&self) -> Result<(), MixminingDbError> {
+        if let Some(store) = &self.store {
+            store.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Adds the mixnode, replacing whatever was previously known about the mixnode
+    /// with the same public key. If it can't be persisted, nothing changes.
+    pub fn add(&mut self, mixnode: Mixnode) -> Result<(), MixminingDbError> {
+        self.persist(MIXNODES_TREE, mixnode.public_key.as_bytes(), &mixnode)?;
+        self.mixnodes.insert(mixnode.public_key.clone(), mixnode);
+        Ok(())
+    }
+
+    /// All announced mixnodes, ordered by their public keys.
+    pub fn get_mixnodes(&self) -> Vec<&Mixnode> {
+        self.mixnodes.values().collect()
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) -> Result<(), MixminingDbError> {
+        self.persist(METADATA_TREE, CAPACITY_KEY, &capacity)?;
         self.capacity = capacity;
+        Ok(())
     }
 
     pub fn capacity(&self) -> usize {
         self.capacity
     }
+
+    /// Record the results of a whole network monitor run, submitted by the monitor with
+    /// the given public key. To keep replayed or delayed reports from being applied,
+    /// a report has to be newer than the last one accepted from the same monitor.
+    /// A report with any result a monitor couldn't have observed is rejected as a whole.
+    /// Either all of the report's measurements are recorded or, if anything fails, none of them.
+    pub fn apply_report(&mut self, monitor: &str, report: Report) -> Result<(), MixminingDbError> {
+        for result in &report.results {
+            result
+                .validate()
+                .map_err(|err| MixminingDbError::InvalidResult(result.public_key.clone(), err))?;
+        }
+        if let Some(&last_accepted) = self.monitor_reports.get(monitor) {
+            if report.timestamp <= last_accepted {
+                return Err(MixminingDbError::StaleReport(last_accepted));
+            }
+        }
+
+        let timestamp = report.timestamp;
+        let measurements = report.results.into_iter().map(|result| {
+            let measurement = Measurement {
+                timestamp,
+                packets_sent: result.packets_sent,
+                packets_received: result.packets_received,
+            };
+            (result.public_key, result.layer, measurement)
+        });
+        self.update_reputations(measurements, Some((monitor, timestamp)))
+    }
+
+    /// Record the measurements of a block committed by Tendermint, along with the state
+    /// of the chain after that block. As the state may depend on the outcome of the block,
+    /// it's derived from the reputations of all mixnodes once the measurements are taken into
+    /// account. Either the whole block is recorded or, if anything fails, none of it.
+    pub fn commit_block<I, F>(
+        &mut self,
+        measurements: I,
+        chain_state: F,
+    ) -> Result<(), MixminingDbError>
+    where
+        I: IntoIterator<Item = (String, u64, Measurement)>,
+        F: FnOnce(&[Reputation]) -> ChainState,
+    {
+        let (reputations, batch) = self.updated_reputations(measurements);
+        let chain_state = chain_state(&reputations.values().cloned().collect::<Vec<_>>());
+        self.store_reputations(reputations, batch, None, Some(chain_state))
+    }
+
+    /// State of the chain as of the last committed block.
+    pub fn chain_state(&self) -> &ChainState {
+        &self.chain_state
+    }
+
+    // Folds the measurements into the reputations of the measured mixnodes. If they come from
+    // a monitor report, it also remembers when that monitor's report was taken.
+    fn update_reputations<I>(
+        &mut self,
+        measurements: I,
+        report: Option<(&str, u64)>,
+    ) -> Result<(), MixminingDbError>
+    where
+        I: IntoIterator<Item = (String, u64, Measurement)>,
+    {
+        let (reputations, batch) = self.updated_reputations(measurements);
+        self.store_reputations(reputations, batch, report, None)
+    }
+
+    // Reputations of all mixnodes after taking the measurements into account, along with
+    // the batch of records to persist them. Nothing is changed yet.
+    fn updated_reputations<I>(&self, measurements: I) -> (BTreeMap<String, Reputation>, sled::Batch)
+    where
+        I: IntoIterator<Item = (String, u64, Measurement)>,
+    {
+        // work on a copy, so that nothing changes until all of the measurements are processed
+        let mut reputations = self.reputations.clone();
+        let mut batch = sled::Batch::default();
+        for (public_key, layer, measurement) in measurements {
+            let reputation = self.updated_reputation(
+                reputations.get(&public_key),
+                &public_key,
+                layer,
+                measurement,
+            );
+            batch.insert(public_key.as_bytes(), encode_record(&reputation));
+            reputations.insert(public_key, reputation);
+        }
+        (reputations, batch)
+    }
+
+    // Replaces the reputations with the updated ones. If the measurements come from a monitor
+    // report, it also remembers when that report was taken, while if they come from
+    // a committed block, the new state of the chain is stored along with them.
+    fn store_reputations(
+        &mut self,
+        reputations: BTreeMap<String, Reputation>,
+        batch: sled::Batch,
+        report: Option<(&str, u64)>,
+        chain_state: Option<ChainState>,
+    ) -> Result<(), MixminingDbError> {
+        self.store_reputations_unless(reputations, batch, report, chain_state, || Ok(()))
+    }
+
+    // Does the work of `store_reputations`, except that `abort` gets to fail the transaction
+    // once everything has been written to it, rather than letting it commit. That's how
+    // the tests make sure a transaction failing partway through doesn't change anything.
+    fn store_reputations_unless<F>(
+        &mut self,
+        reputations: BTreeMap<String, Reputation>,
+        batch: sled::Batch,
+        report: Option<(&str, u64)>,
+        chain_state: Option<ChainState>,
+        abort: F,
+    ) -> Result<(), MixminingDbError>
+    where
+        F: Fn() -> Result<(), sled::Error>,
+    {
+        if let Some(store) = &self.store {
+            // all trees are updated in a single transaction, so a crash can't leave the report
+            // half-written. If the transaction failed, nothing has changed on disk, so nothing
+            // may change in memory either
+            let reputations_tree = store.open_tree(REPUTATIONS_TREE)?;
+            let monitors_tree = store.open_tree(MONITORS_TREE)?;
+            let metadata_tree = store.open_tree(METADATA_TREE)?;
+            (&reputations_tree, &monitors_tree, &metadata_tree)
+                .transaction(|(reputations_tree, monitors_tree, metadata_tree)| {
+                    reputations_tree.apply_batch(&batch)?;
+                    if let Some((monitor, timestamp)) = report {
+                        monitors_tree.insert(monitor.as_bytes(), encode_record(&timestamp))?;
+                    }
+                    if let Some(chain_state) = &chain_state {
+                        metadata_tree.insert(CHAIN_STATE_KEY, encode_record(chain_state))?;
+                    }
+                    abort().map_err(ConflictableTransactionError::Abort)?;
+                    Ok(())
+                })
+                .map_err(|err| match err {
+                    TransactionError::Abort(err) | TransactionError::Storage(err) => err,
+                })?;
+            // once committed, the transaction is part of the store whether or not it's been
+            // flushed yet, so reporting a failure here would only get the caller to retry
+            // something that has already been recorded
+            if let Err(err) = store.flush() {
+                error!("Failed to flush the mixmining database - {}", err);
+            }
+        }
+
+        self.reputations = reputations;
+        if let Some((monitor, timestamp)) = report {
+            self.monitor_reports.insert(monitor.to_owned(), timestamp);
+        }
+        if let Some(chain_state) = chain_state {
+            self.chain_state = chain_state;
+        }
+        Ok(())
+    }
+
+    /// Reputation of a single mixnode, if it has ever been measured.
+    pub fn get_reputation(&self, public_key: &str) -> Option<&Reputation> {
+        self.reputations.get(public_key)
+    }
+
+    /// Reputations of all measured mixnodes, ordered by their public keys.
+    pub fn get_reputations(&self) -> Vec<&Reputation> {
+        self.reputations.values().collect()
+    }
+
+    /// The reputation of a mixnode after taking a new measurement into account.
+    /// Monitors report independently of each other, so the measurement may well be older than
+    /// the latest one known. It only replaces the latest measurement (and the layer) if it's
+    /// newer, and the time the mixnode was last seen healthy never moves backwards.
+    /// The measurement is also put into the mixnode's recent history, which is ordered by time
+    /// and holds at most `history_depth` entries, and folded into its smoothed reliability.
+    fn updated_reputation(
+        &self,
+        previous: Option<&Reputation>,
+        public_key: &str,
+        layer: u64,
+        measurement: Measurement,
+    ) -> Reputation {
+        let previous_healthy = previous.and_then(|previous| previous.last_healthy);
+        let last_healthy = if measurement.is_healthy() {
+            std::cmp::max(previous_healthy, Some(measurement.timestamp))
+        } else {
+            previous_healthy
+        };
+        let mut recent = previous
+            .map(|previous| previous.recent.clone())
+            .unwrap_or_default();
+        let position = recent
+            .iter()
+            .rposition(|recent| recent.timestamp <= measurement.timestamp)
+            .map_or(0, |position| position + 1);
+        recent.insert(position, measurement.clone());
+        if recent.len() > self.history_depth {
+            recent.drain(..recent.len() - self.history_depth);
+        }
+
+        let reliability = measurement.reliability();
+        let previous_ema = previous.and_then(|previous| {
+            previous
+                .reliability_ema
+                .map(|ema| (previous.last_tested(), ema))
+        });
+        let reliability_ema = match previous_ema {
+            // whatever a mixnode did before a long absence says little about how it's doing now,
+            // so the average is started over rather than dragging the old behaviour along
+            Some((last_tested, ema))
+                if measurement.timestamp.saturating_sub(last_tested)
+                    <= self.smoothing_reset_after =>
+            {
+                self.smoothing_factor * reliability + (1.0 - self.smoothing_factor) * ema
+            }
+            _ => reliability,
+        };
+
+        let (layer, latest) = match previous {
+            Some(previous) if previous.last_tested() > measurement.timestamp => {
+                (previous.layer, previous.latest.clone())
+            }
+            _ => (layer, measurement),
+        };
+
+        Reputation {
+            public_key: public_key.to_owned(),
+            layer,
+            latest,
+            recent,
+            last_healthy,
+            reliability_ema: Some(reliability_ema),
+        }
+    }
+}
+
+// Records a single measurement, for tests that don't care about reports.
+#[cfg(test)]
+fn record(db: &mut MixminingDb, public_key: &str, layer: u64, measurement: Measurement) {
+    db.update_reputations(vec![(public_key.to_owned(), layer, measurement)], None)
+        .unwrap();
 }
 
 #[cfg(test)]
@@ -47,7 +524,7 @@ mod capacity {
     #[test]
     fn setting_and_getting_work() {
         let mut db = MixminingDb::new();
-        db.set_capacity(1);
+        db.set_capacity(1).unwrap();
         assert_eq!(1, db.capacity());
     }
 }
@@ -55,28 +532,39 @@ mod capacity {
 #[cfg(test)]
 mod adding_and_retrieving_mixnodes {
     use super::*;
+    use crate::services::mixmining::tests::fake_mixnode;
 
     #[test]
     fn add_and_retrieve_one_works() {
-        let node = fake_mixnode("London, UK");
+        let node = fake_mixnode("abc123", "London, UK");
         let mut db = MixminingDb::new();
 
-        db.add(node.clone());
+        db.add(node.clone()).unwrap();
 
-        assert_eq!(&node, db.get_mixnodes().first().unwrap());
+        assert_eq!(&node, *db.get_mixnodes().first().unwrap());
     }
 
     #[test]
     fn add_and_retrieve_two_works() {
-        let node1 = fake_mixnode("London, UK");
-        let node2 = fake_mixnode("Neuchatel");
+        let node1 = fake_mixnode("abc123", "London, UK");
+        let node2 = fake_mixnode("def456", "Neuchatel");
+        let mut db = MixminingDb::new();
+
+        db.add(node2.clone()).unwrap();
+        db.add(node1.clone()).unwrap();
+
+        assert_eq!(vec![&node1, &node2], db.get_mixnodes());
+    }
+
+    #[test]
+    fn adding_known_mixnode_replaces_it() {
         let mut db = MixminingDb::new();
 
-        db.add(node1.clone());
-        db.add(node2.clone());
+        db.add(fake_mixnode("abc123", "London, UK")).unwrap();
+        db.add(fake_mixnode("abc123", "Neuchatel")).unwrap();
 
-        assert_eq!(node1, db.get_mixnodes()[0]);
-        assert_eq!(node2, db.get_mixnodes()[1]);
+        assert_eq!(1, db.get_mixnodes().len());
+        assert_eq!("Neuchatel", db.get_mixnodes()[0].location);
     }
 
     #[test]
@@ -88,18 +576,559 @@ mod adding_and_retrieving_mixnodes {
     #[test]
     fn calling_list_when_empty_returns_empty_vec() {
         let db = MixminingDb::new();
-        let empty: Vec<Mixnode> = vec![];
-        assert_eq!(&empty, db.get_mixnodes());
+        assert!(db.get_mixnodes().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod recording_measurements {
+    use super::*;
+    use crate::services::mixmining::tests::measurement;
+
+    #[test]
+    fn starts_with_no_reputations() {
+        let db = MixminingDb::new();
+        assert!(db.get_reputations().is_empty());
+    }
+
+    #[test]
+    fn recorded_measurement_can_be_retrieved() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+
+        let reputation = &db.reputations["abc123"];
+        assert_eq!("abc123", reputation.public_key);
+        assert_eq!(2, reputation.layer);
+        assert_eq!(measurement(100, 7), reputation.latest);
+    }
+
+    #[test]
+    fn newer_measurement_replaces_older_one() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+        record(&mut db, "abc123", 3, measurement(200, 9));
+
+        assert_eq!(1, db.reputations.len());
+        let reputation = &db.reputations["abc123"];
+        assert_eq!(3, reputation.layer);
+        assert_eq!(measurement(200, 9), reputation.latest);
+    }
+
+    #[test]
+    fn healthy_measurement_updates_last_healthy() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+
+        let reputation = &db.reputations["abc123"];
+        assert_eq!(100, reputation.last_tested());
+        assert_eq!(Some(100), reputation.last_healthy);
+    }
+
+    #[test]
+    fn unhealthy_measurement_keeps_previous_last_healthy() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+        record(&mut db, "abc123", 2, measurement(200, 0));
+
+        let reputation = &db.reputations["abc123"];
+        assert_eq!(200, reputation.last_tested());
+        assert_eq!(Some(100), reputation.last_healthy);
+    }
+
+    #[test]
+    fn older_measurement_does_not_replace_latest_one() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(200, 0));
+        record(&mut db, "abc123", 3, measurement(100, 7));
+
+        let reputation = &db.reputations["abc123"];
+        assert_eq!(2, reputation.layer);
+        assert_eq!(measurement(200, 0), reputation.latest);
+        assert_eq!(Some(100), reputation.last_healthy);
+    }
+
+    #[test]
+    fn older_healthy_measurement_does_not_move_last_healthy_back() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(200, 7));
+        record(&mut db, "abc123", 2, measurement(100, 7));
+
+        assert_eq!(Some(200), db.reputations["abc123"].last_healthy);
+    }
+
+    #[test]
+    fn never_healthy_node_has_no_last_healthy() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 0));
+
+        assert_eq!(None, db.reputations["abc123"].last_healthy);
+    }
+
+    #[test]
+    fn measurements_are_kept_in_recent_history() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+        record(&mut db, "abc123", 2, measurement(200, 9));
+
+        assert_eq!(
+            vec![measurement(100, 7), measurement(200, 9)],
+            db.reputations["abc123"].recent
+        );
+    }
+
+    #[test]
+    fn recent_history_drops_oldest_measurements_when_full() {
+        let mut db = MixminingDb::new();
+        for timestamp in 0..DEFAULT_HISTORY_DEPTH as u64 + 2 {
+            record(&mut db, "abc123", 2, measurement(timestamp, 7));
+        }
+
+        let recent = &db.reputations["abc123"].recent;
+        assert_eq!(DEFAULT_HISTORY_DEPTH, recent.len());
+        assert_eq!(2, recent[0].timestamp);
+        assert_eq!(
+            DEFAULT_HISTORY_DEPTH as u64 + 1,
+            recent.last().unwrap().timestamp
+        );
+    }
+
+    #[test]
+    fn older_measurement_is_put_in_its_place_in_recent_history() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+        record(&mut db, "abc123", 2, measurement(300, 9));
+        record(&mut db, "abc123", 2, measurement(200, 8));
+
+        assert_eq!(
+            vec![
+                measurement(100, 7),
+                measurement(200, 8),
+                measurement(300, 9)
+            ],
+            db.reputations["abc123"].recent
+        );
+    }
+
+    #[test]
+    fn measurement_older_than_a_full_recent_history_is_left_out() {
+        let mut db = MixminingDb::new().with_history_depth(2);
+        record(&mut db, "abc123", 2, measurement(200, 7));
+        record(&mut db, "abc123", 2, measurement(300, 7));
+        record(&mut db, "abc123", 2, measurement(100, 7));
+
+        let timestamps: Vec<_> = db.reputations["abc123"]
+            .recent
+            .iter()
+            .map(|measurement| measurement.timestamp)
+            .collect();
+        assert_eq!(vec![200, 300], timestamps);
+    }
+
+    #[test]
+    fn history_depth_can_be_configured() {
+        let mut db = MixminingDb::new().with_history_depth(2);
+        for timestamp in 0..5 {
+            record(&mut db, "abc123", 2, measurement(timestamp, 7));
+        }
+
+        let timestamps: Vec<_> = db.reputations["abc123"]
+            .recent
+            .iter()
+            .map(|measurement| measurement.timestamp)
+            .collect();
+        assert_eq!(vec![3, 4], timestamps);
+    }
+
+    #[test]
+    fn first_measurement_seeds_smoothed_reliability() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+
+        assert_eq!(Some(0.7), db.reputations["abc123"].reliability_ema);
+    }
+
+    #[test]
+    fn smoothed_reliability_moves_towards_new_measurements() {
+        let mut db = MixminingDb::new().with_reliability_smoothing(0.5, 1000);
+        record(&mut db, "abc123", 2, measurement(100, 10));
+        record(&mut db, "abc123", 2, measurement(200, 0));
+        record(&mut db, "abc123", 2, measurement(300, 0));
+
+        let reputation = &db.reputations["abc123"];
+        assert_eq!(0.25, reputation.smoothed_reliability());
+        assert_eq!(0.0, reputation.latest.reliability());
+    }
+
+    #[test]
+    fn smoothed_reliability_starts_over_after_long_absence() {
+        let mut db = MixminingDb::new().with_reliability_smoothing(0.5, 1000);
+        record(&mut db, "abc123", 2, measurement(100, 0));
+        record(&mut db, "abc123", 2, measurement(1100, 10));
+        assert_eq!(0.5, db.reputations["abc123"].smoothed_reliability());
+
+        record(&mut db, "abc123", 2, measurement(2101, 10));
+        assert_eq!(1.0, db.reputations["abc123"].smoothed_reliability());
+    }
+
+    #[test]
+    fn smoothed_reliability_falls_back_to_latest_for_old_records() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+        let mut reputation = db.reputations["abc123"].clone();
+        reputation.reliability_ema = None;
+
+        assert_eq!(0.7, reputation.smoothed_reliability());
+    }
+
+    #[test]
+    fn unknown_mixnode_has_no_reputation() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "abc123", 2, measurement(100, 7));
+        assert!(db.get_reputation("def456").is_none());
+        assert!(db.get_reputation("abc123").is_some());
+    }
+
+    #[test]
+    fn reputations_are_ordered_by_public_key() {
+        let mut db = MixminingDb::new();
+        record(&mut db, "def456", 1, measurement(100, 7));
+        record(&mut db, "abc123", 2, measurement(100, 7));
+
+        let keys: Vec<_> = db
+            .get_reputations()
+            .into_iter()
+            .map(|reputation| reputation.public_key.clone())
+            .collect();
+        assert_eq!(vec!["abc123".to_string(), "def456".to_string()], keys);
+    }
+}
+
+#[cfg(test)]
+mod applying_reports {
+    use super::*;
+    use crate::services::mixmining::tests::{fake_node_test_result, node_key, MONITOR_KEY};
+
+    fn report(timestamp: u64) -> Report {
+        Report {
+            timestamp,
+            results: vec![fake_node_test_result("abc123", 1, 7)],
+        }
+    }
+
+    #[test]
+    fn newer_reports_from_the_same_monitor_are_applied() {
+        let mut db = MixminingDb::new();
+        db.apply_report(MONITOR_KEY, report(100)).unwrap();
+        db.apply_report(MONITOR_KEY, report(200)).unwrap();
+        let reputation = db.get_reputation(&node_key("abc123")).unwrap();
+        assert_eq!(200, reputation.latest.timestamp);
+        assert_eq!(2, reputation.recent.len());
+    }
+
+    #[test]
+    fn replayed_report_is_rejected() {
+        let mut db = MixminingDb::new();
+        db.apply_report(MONITOR_KEY, report(100)).unwrap();
+        let before = db.clone();
+
+        match db.apply_report(MONITOR_KEY, report(100)) {
+            Err(MixminingDbError::StaleReport(100)) => (),
+            _ => panic!("expected the replayed report to be rejected"),
+        }
+        assert_eq!(before, db);
+    }
+
+    #[test]
+    fn report_older_than_the_last_one_from_the_same_monitor_is_rejected() {
+        let mut db = MixminingDb::new();
+        db.apply_report(MONITOR_KEY, report(200)).unwrap();
+        let before = db.clone();
+
+        match db.apply_report(MONITOR_KEY, report(100)) {
+            Err(MixminingDbError::StaleReport(200)) => (),
+            _ => panic!("expected the delayed report to be rejected"),
+        }
+        assert_eq!(before, db);
+    }
+
+    #[test]
+    fn reports_from_different_monitors_are_ordered_independently() {
+        let mut db = MixminingDb::new();
+        db.apply_report(MONITOR_KEY, report(200)).unwrap();
+        db.apply_report("monitor456", report(100)).unwrap();
+        assert_eq!(
+            2,
+            db.get_reputation(&node_key("abc123")).unwrap().recent.len()
+        );
     }
 
-    fn fake_mixnode(location: &str) -> Mixnode {
-        Mixnode {
-            host: String::from("foo.com"),
-            last_seen: 123,
-            location: String::from(location),
-            public_key: String::from("abc123"),
-            stake: 8,
-            version: String::from("1.0"),
+    #[test]
+    fn delayed_report_from_another_monitor_does_not_move_last_tested_back() {
+        let mut db = MixminingDb::new();
+        db.apply_report(MONITOR_KEY, report(200)).unwrap();
+        db.apply_report("monitor456", report(100)).unwrap();
+
+        let reputation = db.get_reputation(&node_key("abc123")).unwrap();
+        assert_eq!(200, reputation.last_tested());
+        assert_eq!(Some(200), reputation.last_healthy);
+    }
+
+    #[test]
+    fn delayed_report_from_another_monitor_is_kept_in_time_order() {
+        let mut db = MixminingDb::new();
+        db.apply_report(MONITOR_KEY, report(200)).unwrap();
+        db.apply_report("monitor456", report(100)).unwrap();
+
+        let timestamps: Vec<_> = db
+            .get_reputation(&node_key("abc123"))
+            .unwrap()
+            .recent
+            .iter()
+            .map(|measurement| measurement.timestamp)
+            .collect();
+        assert_eq!(vec![100, 200], timestamps);
+    }
+
+    #[test]
+    fn report_with_an_impossible_result_is_rejected_as_a_whole() {
+        let mut db = MixminingDb::new();
+        let mut report = report(100);
+        report.results.push(fake_node_test_result("def456", 2, 11));
+
+        match db.apply_report(MONITOR_KEY, report) {
+            Err(MixminingDbError::InvalidResult(public_key, InvalidMeasurement::Packets(_))) => {
+                assert_eq!(node_key("def456"), public_key)
+            }
+            _ => panic!("expected the report to be rejected"),
+        }
+        assert_eq!(MixminingDb::new(), db);
+    }
+
+    #[test]
+    fn report_with_a_malformed_public_key_is_rejected() {
+        let mut db = MixminingDb::new();
+        let mut report = report(100);
+        report.results[0].public_key = "".to_owned();
+
+        match db.apply_report(MONITOR_KEY, report) {
+            Err(MixminingDbError::InvalidResult(_, InvalidMeasurement::NodeKey(_))) => (),
+            _ => panic!("expected the report to be rejected"),
+        }
+        assert!(db.get_mixnodes().is_empty());
+        assert!(db.get_reputations().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod persistence {
+    use super::*;
+    use crate::services::mixmining::tests::{
+        fake_mixnode, fake_node_test_result, measurement, node_key, MONITOR_KEY,
+    };
+
+    // Opens the database at `path`, lets `write` change it and closes it again, so that
+    // everything is on disk and the store is unlocked by the time this returns.
+    fn write_to<F>(path: &Path, write: F)
+    where
+        F: FnOnce(&mut MixminingDb),
+    {
+        let mut db = MixminingDb::open(path).unwrap();
+        write(&mut db);
+        db.flush().unwrap();
+    }
+
+    #[test]
+    fn reopened_database_contains_previously_written_data() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("mixmining.db");
+
+        let changes = |db: &mut MixminingDb| {
+            db.add(fake_mixnode("abc123", "London, UK")).unwrap();
+            db.add(fake_mixnode("def456", "Neuchatel")).unwrap();
+            db.set_capacity(3).unwrap();
+            record(db, "abc123", 2, measurement(100, 7));
+        };
+        let mut expected = MixminingDb::new();
+        changes(&mut expected);
+        write_to(&db_path, changes);
+
+        let reopened = MixminingDb::open(&db_path).unwrap();
+        assert_eq!(expected, reopened);
+        assert_eq!("London, UK", reopened.get_mixnodes()[0].location);
+        assert_eq!("Neuchatel", reopened.get_mixnodes()[1].location);
+    }
+
+    #[test]
+    fn reannounced_mixnode_is_stored_only_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("mixmining.db");
+
+        write_to(&db_path, |db| {
+            db.add(fake_mixnode("abc123", "London, UK")).unwrap();
+            db.add(fake_mixnode("abc123", "Neuchatel")).unwrap();
+        });
+
+        let reopened = MixminingDb::open(&db_path).unwrap();
+        assert_eq!(1, reopened.get_mixnodes().len());
+        assert_eq!("Neuchatel", reopened.get_mixnodes()[0].location);
+    }
+
+    #[test]
+    fn flushing_keeps_the_data() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("mixmining.db");
+
+        write_to(&db_path, |db| db.set_capacity(3).unwrap());
+
+        assert_eq!(3, MixminingDb::open(&db_path).unwrap().capacity());
+    }
+
+    #[test]
+    fn opened_database_is_reachable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = MixminingDb::open(temp_dir.path().join("mixmining.db")).unwrap();
+        assert!(db.is_reachable());
+    }
+
+    #[test]
+    fn opening_empty_database_gives_defaults() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = MixminingDb::open(temp_dir.path().join("mixmining.db")).unwrap();
+        assert_eq!(MixminingDb::new(), db);
+    }
+
+    #[test]
+    fn records_with_unknown_fields_can_still_be_decoded() {
+        let record = br#"{"version":1,"data":42,"some_future_field":"foomp"}"#;
+        assert_eq!(42usize, decode_record::<usize>(record).unwrap());
+    }
+
+    #[test]
+    fn records_from_newer_versions_are_rejected() {
+        let record = br#"{"version":2,"data":42}"#;
+        match decode_record::<usize>(record) {
+            Err(MixminingDbError::UnsupportedRecordVersion(2)) => (),
+            _ => panic!("expected the record version to be rejected"),
+        }
+    }
+
+    #[test]
+    fn applied_report_is_persisted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("mixmining.db");
+
+        let changes = |db: &mut MixminingDb| {
+            db.apply_report(
+                MONITOR_KEY,
+                Report {
+                    timestamp: 100,
+                    results: vec![
+                        fake_node_test_result("abc123", 1, 7),
+                        fake_node_test_result("def456", 2, 9),
+                    ],
+                },
+            )
+            .unwrap();
+        };
+        let mut expected = MixminingDb::new();
+        changes(&mut expected);
+        write_to(&db_path, changes);
+
+        let reopened = MixminingDb::open(&db_path).unwrap();
+        assert_eq!(expected, reopened);
+        assert_eq!(2, reopened.get_reputations().len());
+    }
+
+    #[test]
+    fn committed_block_is_persisted_along_with_the_chain_state() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("mixmining.db");
+
+        let mut nonces = BTreeMap::new();
+        nonces.insert(MONITOR_KEY.to_owned(), 3);
+        write_to(&db_path, |db| {
+            db.commit_block(
+                vec![("abc123".to_owned(), 1, measurement(100, 7))],
+                |reputations| ChainState {
+                    height: 1,
+                    // the state gets to see the reputations resulting from the block
+                    app_hash: reputations[0].public_key.clone().into_bytes(),
+                    nonces: nonces.clone(),
+                    ..ChainState::default()
+                },
+            )
+            .unwrap()
+        });
+
+        let reopened = MixminingDb::open(&db_path).unwrap();
+        let chain_state = reopened.chain_state();
+        assert_eq!(1, chain_state.height);
+        assert_eq!(b"abc123".to_vec(), chain_state.app_hash);
+        assert_eq!(nonces, chain_state.nonces);
+        assert_eq!(1, reopened.get_reputations().len());
+    }
+
+    #[test]
+    fn failed_transaction_leaves_the_prior_state_intact() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("mixmining.db");
+
+        let mut before = MixminingDb::new();
+        write_to(&db_path, |db| {
+            db.apply_report(
+                MONITOR_KEY,
+                Report {
+                    timestamp: 100,
+                    results: vec![fake_node_test_result("abc123", 1, 7)],
+                },
+            )
+            .unwrap();
+            before = MixminingDb {
+                store: None,
+                ..db.clone()
+            };
+
+            let (reputations, batch) = db.updated_reputations(vec![
+                (node_key("abc123"), 1, measurement(200, 0)),
+                (node_key("def456"), 2, measurement(200, 9)),
+            ]);
+            let chain_state = ChainState {
+                height: 1,
+                ..ChainState::default()
+            };
+            let result = db.store_reputations_unless(
+                reputations,
+                batch,
+                Some((MONITOR_KEY, 200)),
+                Some(chain_state),
+                || Err(sled::Error::Unsupported("simulated failure".to_owned())),
+            );
+            assert!(result.is_err());
+            assert_eq!(before, *db);
+        });
+
+        assert_eq!(before, MixminingDb::open(&db_path).unwrap());
+    }
+
+    #[test]
+    fn replayed_report_is_still_rejected_after_reopening() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("mixmining.db");
+
+        let report = Report {
+            timestamp: 100,
+            results: vec![fake_node_test_result("abc123", 1, 7)],
+        };
+        write_to(&db_path, |db| {
+            db.apply_report(MONITOR_KEY, report.clone()).unwrap()
+        });
+
+        // the monitor's timestamp is written in the same transaction as the report's measurements
+        let mut reopened = MixminingDb::open(&db_path).unwrap();
+        match reopened.apply_report(MONITOR_KEY, report) {
+            Err(MixminingDbError::StaleReport(100)) => (),
+            _ => panic!("expected the replayed report to be rejected"),
         }
+        assert_eq!(1, reopened.get_reputations()[0].recent.len());
     }
 }
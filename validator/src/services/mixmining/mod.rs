@@ -14,13 +14,38 @@
 
 use db::MixminingDb;
 use models::*;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub mod db;
 pub mod models;
-mod tests;
+#[cfg(test)]
+pub mod tests;
+
+/// Reliability below which a measurement is considered bad, unless configured otherwise.
+pub const DEFAULT_RELIABILITY_THRESHOLD: f64 = 0.5;
+
+/// Number of consecutive bad measurements after which a mixnode is underperforming,
+/// unless configured otherwise.
+pub const DEFAULT_UNDERPERFORMING_SWEEPS: usize = 3;
+
+/// Number of layers the mixnet is made of, unless configured otherwise.
+pub const DEFAULT_MIX_LAYERS: u64 = 3;
+
+/// Number of healthy mixnodes a layer needs not to be underpopulated, unless configured otherwise.
+pub const DEFAULT_MINIMUM_LAYER_SIZE: usize = 3;
+
+/// How long ago (in milliseconds) a mixnode can have last been measured and still count towards
+/// the population of its layer, unless configured otherwise.
+pub const DEFAULT_LAYER_BALANCE_MAX_AGE: u64 = 60 * 60 * 1000;
 
 pub struct Service {
     db: MixminingDb,
+    reliability_threshold: f64,
+    underperforming_sweeps: usize,
+    mix_layers: u64,
+    minimum_layer_size: usize,
+    layer_balance_max_age: u64,
 }
 
 /// The mixmining::Service provides logic for updating and slashing mixnode
@@ -49,23 +74,59 @@ pub struct Service {
 /// and will be dramatically reworked over the next few months.
 impl Service {
     pub fn new(db: MixminingDb) -> Service {
-        Service { db }
+        Service {
+            db,
+            reliability_threshold: DEFAULT_RELIABILITY_THRESHOLD,
+            underperforming_sweeps: DEFAULT_UNDERPERFORMING_SWEEPS,
+            mix_layers: DEFAULT_MIX_LAYERS,
+            minimum_layer_size: DEFAULT_MINIMUM_LAYER_SIZE,
+            layer_balance_max_age: DEFAULT_LAYER_BALANCE_MAX_AGE,
+        }
+    }
+
+    /// Sets when a mixnode is deemed to be underperforming: once its reliability has been below
+    /// `reliability_threshold` in each of its last `sweeps` measurements. The number of sweeps
+    /// is capped at the depth of the measurement history kept by the database.
+    pub fn with_underperformance_criteria(
+        mut self,
+        reliability_threshold: f64,
+        sweeps: usize,
+    ) -> Self {
+        self.reliability_threshold = reliability_threshold;
+        self.underperforming_sweeps = std::cmp::min(sweeps, self.db.history_depth());
+        self
+    }
+
+    /// Sets how many layers the mixnet has and how many healthy mixnodes each of them
+    /// needs in order not to be considered underpopulated. Mixnodes last measured more than
+    /// `max_age` milliseconds ago don't count towards the population of their layer.
+    pub fn with_layer_balance_criteria(
+        mut self,
+        mix_layers: u64,
+        minimum_layer_size: usize,
+        max_age: u64,
+    ) -> Self {
+        self.mix_layers = mix_layers;
+        self.minimum_layer_size = minimum_layer_size;
+        self.layer_balance_max_age = max_age;
+        self
     }
 
     // Add a mixnode so that it becomes part of the possible mixnode set.
-    pub fn add(&mut self, mixnode: Mixnode) {
-        self.db.add(mixnode);
+    // A mixnode announcing itself again replaces what was previously known about it.
+    pub fn add(&mut self, mixnode: Mixnode) -> Result<(), db::MixminingDbError> {
+        self.db.add(mixnode)
     }
 
     pub fn topology(&self) -> Topology {
-        let mixnodes = self.db.get_mixnodes();
+        let mixnodes = self.db.get_mixnodes().into_iter().cloned().collect();
         let service_providers: Vec<ServiceProvider> = vec![];
         let validators: Vec<Validator> = vec![];
-        Topology::new(mixnodes.to_vec(), service_providers, validators)
+        Topology::new(mixnodes, service_providers, validators)
     }
 
-    pub fn set_capacity(&mut self, capacity: usize) {
-        self.db.set_capacity(capacity);
+    pub fn set_capacity(&mut self, capacity: usize) -> Result<(), db::MixminingDbError> {
+        self.db.set_capacity(capacity)
     }
 
     /// A fake capacity, so we can take the top n mixnodes based on stake
@@ -73,6 +134,169 @@ impl Service {
         self.db.capacity()
     }
 
+    /// Record the results of a network monitor run against the measured mixnodes, submitted by
+    /// the monitor with the given public key. Reports that aren't newer than the last one from
+    /// the same monitor are rejected, just like reports with results no monitor could have
+    /// observed.
+    pub fn apply_report(
+        &mut self,
+        monitor: &str,
+        report: Report,
+    ) -> Result<(), db::MixminingDbError> {
+        self.db.apply_report(monitor, report)
+    }
+
+    /// Record the measurements of a block committed by Tendermint, along with the state
+    /// of the chain after that block, derived from the resulting reputations of all mixnodes.
+    pub fn commit_block<F>(
+        &mut self,
+        measurements: Vec<(String, u64, Measurement)>,
+        chain_state: F,
+    ) -> Result<(), db::MixminingDbError>
+    where
+        F: FnOnce(&[Reputation]) -> ChainState,
+    {
+        self.db.commit_block(measurements, chain_state)
+    }
+
+    /// Record the state the chain starts off with, as set in its genesis.
+    pub fn init_chain(&mut self, chain_state: ChainState) -> Result<(), db::MixminingDbError> {
+        self.db.commit_block(Vec::new(), |_| chain_state)
+    }
+
+    /// State of the chain as of the last block committed by Tendermint.
+    pub fn chain_state(&self) -> ChainState {
+        self.db.chain_state().clone()
+    }
+
+    /// Unix timestamp (in milliseconds) of the newest measurement in the database, so that
+    /// it's still known after a restart.
+    pub fn last_measurement_recorded(&self) -> Option<u64> {
+        self.db
+            .get_reputations()
+            .into_iter()
+            .map(|reputation| reputation.latest.timestamp)
+            .max()
+    }
+
+    /// Makes sure all recorded data has been written to disk.
+    pub fn flush(&self) -> Result<(), db::MixminingDbError> {
+        self.db.flush()
+    }
+
+    pub fn is_db_reachable(&self) -> bool {
+        self.db.is_reachable()
+    }
+
+    /// Reputations of all measured mixnodes, optionally restricted to a single mixnet layer.
+    pub fn reputations(&self, layer: Option<u64>) -> Vec<Reputation> {
+        self.db
+            .get_reputations()
+            .into_iter()
+            .filter(|reputation| match layer {
+                Some(layer) => reputation.layer == layer,
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Reputation of the given mixnode, or `None` if it has never been measured.
+    pub fn reputation(&self, public_key: &str) -> Option<Reputation> {
+        self.db.get_reputation(public_key).cloned()
+    }
+
+    /// Most recent measurements of the given mixnode, oldest first,
+    /// or `None` if the mixnode has never been measured.
+    pub fn history(&self, public_key: &str) -> Option<Vec<Measurement>> {
+        self.db
+            .get_reputation(public_key)
+            .map(|reputation| reputation.recent.clone())
+    }
+
+    /// Reputations of mixnodes which have consistently fallen below the reliability threshold.
+    /// These are the candidates for removal from the active set.
+    pub fn underperforming(&self) -> Vec<Reputation> {
+        self.db
+            .get_reputations()
+            .into_iter()
+            .filter(|reputation| {
+                reputation
+                    .is_underperforming(self.reliability_threshold, self.underperforming_sweeps)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// A single page of reputations, skipping the first `offset` matching mixnodes and
+    /// returning at most `limit` of them. The total number of matching mixnodes is returned
+    /// alongside so that callers can page through the whole set.
+    pub fn reputations_page(
+        &self,
+        layer: Option<u64>,
+        offset: usize,
+        limit: usize,
+    ) -> Page<Reputation> {
+        let reputations = self.reputations(layer);
+        Page {
+            total: reputations.len(),
+            items: reputations.into_iter().skip(offset).take(limit).collect(),
+        }
+    }
+
+    /// How well each of the mixnet layers is populated, judging by the latest measurements.
+    /// Mixnodes which haven't been measured within the configured maximum age are considered
+    /// gone and aren't counted.
+    /// All of the expected layers are included, even if none of their mixnodes have been
+    /// measured, as well as any other layer mixnodes have been measured on.
+    pub fn layer_balance(&self) -> Vec<LayerBalance> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.layer_balance_at(now)
+    }
+
+    // Layer balance as of `now`, a Unix timestamp in milliseconds.
+    fn layer_balance_at(&self, now: u64) -> Vec<LayerBalance> {
+        let mut layers: BTreeMap<u64, Vec<&Reputation>> = (1..=self.mix_layers)
+            .map(|layer| (layer, Vec::new()))
+            .collect();
+        let cutoff = now.saturating_sub(self.layer_balance_max_age);
+        for reputation in self.db.get_reputations() {
+            if reputation.latest.timestamp < cutoff {
+                continue;
+            }
+            layers.entry(reputation.layer).or_default().push(reputation);
+        }
+
+        layers
+            .into_iter()
+            .map(|(layer, reputations)| {
+                let healthy_mixnodes = reputations
+                    .iter()
+                    .filter(|reputation| reputation.latest.is_healthy())
+                    .count();
+                let reliability = if reputations.is_empty() {
+                    None
+                } else {
+                    let total: f64 = reputations
+                        .iter()
+                        .map(|reputation| reputation.latest.reliability())
+                        .sum();
+                    Some(total / reputations.len() as f64)
+                };
+                LayerBalance {
+                    layer,
+                    mixnodes: reputations.len(),
+                    healthy_mixnodes,
+                    reliability,
+                    underpopulated: healthy_mixnodes < self.minimum_layer_size,
+                }
+            })
+            .collect()
+    }
+
     /*
 
     /// Update (or create) a given mixnode stake, identified by the mixnode's public key
@@ -123,15 +347,15 @@ mod mixnodes {
     fn adding_and_retrieving_works() {
         let mock_db = MixminingDb::new();
         let mut service = Service::new(mock_db);
-        let node1 = tests::fake_mixnode("London, UK");
+        let node1 = tests::fake_mixnode("abc123", "London, UK");
 
-        service.add(node1.clone());
+        service.add(node1.clone()).unwrap();
         let nodes = service.topology().mixnodes;
         assert_eq!(1, nodes.len());
         assert_eq!(node1.clone(), nodes[0]);
-        let node2 = tests::fake_mixnode("Neuchatel");
+        let node2 = tests::fake_mixnode("def456", "Neuchatel");
 
-        service.add(node2.clone());
+        service.add(node2.clone()).unwrap();
         let nodes = service.topology().mixnodes;
         assert_eq!(2, nodes.len());
         assert_eq!(node1, nodes[0]);
@@ -161,7 +385,7 @@ mod capacity {
         let mock_db = db::MixminingDb::new();
         let mut service = Service::new(mock_db);
 
-        service.set_capacity(3);
+        service.set_capacity(3).unwrap();
 
         assert_eq!(3, service.capacity());
     }
@@ -169,8 +393,410 @@ mod capacity {
     #[test]
     fn getting_capacity_works() {
         let mut mock_db = db::MixminingDb::new();
-        mock_db.set_capacity(3);
+        mock_db.set_capacity(3).unwrap();
         let service = Service::new(mock_db);
         assert_eq!(3, service.capacity());
     }
 }
+
+#[cfg(test)]
+mod reports {
+    use super::*;
+
+    #[test]
+    fn no_measurement_is_recorded_initially() {
+        let service = Service::new(MixminingDb::new());
+        assert!(service.last_measurement_recorded().is_none());
+    }
+
+    #[test]
+    fn applying_report_updates_last_measurement_time() {
+        let mut service = Service::new(MixminingDb::new());
+        service
+            .apply_report(
+                tests::MONITOR_KEY,
+                Report {
+                    timestamp: 1234,
+                    results: vec![tests::fake_node_test_result("abc123", 1, 10)],
+                },
+            )
+            .unwrap();
+        assert_eq!(Some(1234), service.last_measurement_recorded());
+    }
+
+    #[test]
+    fn last_measurement_time_is_known_for_previously_measured_database() {
+        let mut db = MixminingDb::new();
+        db.apply_report(
+            tests::MONITOR_KEY,
+            Report {
+                timestamp: 1234,
+                results: vec![tests::fake_node_test_result("abc123", 1, 10)],
+            },
+        )
+        .unwrap();
+        db.apply_report(
+            tests::MONITOR_KEY,
+            Report {
+                timestamp: 5678,
+                results: vec![tests::fake_node_test_result("def456", 2, 10)],
+            },
+        )
+        .unwrap();
+        let service = Service::new(db);
+        assert_eq!(Some(5678), service.last_measurement_recorded());
+    }
+
+    #[test]
+    fn applying_report_records_measurement_for_each_node() {
+        let mut service = Service::new(MixminingDb::new());
+        let report = Report {
+            timestamp: 1234,
+            results: vec![
+                tests::fake_node_test_result("abc123", 1, 10),
+                tests::fake_node_test_result("def456", 2, 4),
+            ],
+        };
+
+        service.apply_report(tests::MONITOR_KEY, report).unwrap();
+
+        let abc123 = service.reputation(&tests::node_key("abc123")).unwrap();
+        assert_eq!(1, abc123.layer);
+        assert_eq!(tests::measurement(1234, 10), abc123.latest);
+        let def456 = service.reputation(&tests::node_key("def456")).unwrap();
+        assert_eq!(2, def456.layer);
+        assert_eq!(tests::measurement(1234, 4), def456.latest);
+        assert_eq!(2, service.reputations(None).len());
+    }
+}
+
+#[cfg(test)]
+mod reputations {
+    use super::*;
+
+    fn service_with_measured_nodes() -> Service {
+        let mut service = Service::new(MixminingDb::new());
+        service
+            .apply_report(
+                tests::MONITOR_KEY,
+                Report {
+                    timestamp: 1234,
+                    results: vec![
+                        tests::fake_node_test_result("abc123", 1, 10),
+                        tests::fake_node_test_result("def456", 2, 4),
+                        tests::fake_node_test_result("ghi789", 1, 0),
+                    ],
+                },
+            )
+            .unwrap();
+        service
+    }
+
+    #[test]
+    fn without_layer_all_nodes_are_returned() {
+        let service = service_with_measured_nodes();
+        let public_keys: Vec<_> = service
+            .reputations(None)
+            .into_iter()
+            .map(|reputation| reputation.public_key)
+            .collect();
+        let expected: Vec<_> = ["abc123", "def456", "ghi789"]
+            .iter()
+            .map(|name| tests::node_key(name))
+            .collect();
+        assert_eq!(expected, public_keys);
+    }
+
+    #[test]
+    fn with_layer_only_nodes_on_that_layer_are_returned() {
+        let service = service_with_measured_nodes();
+        let public_keys: Vec<_> = service
+            .reputations(Some(1))
+            .into_iter()
+            .map(|reputation| reputation.public_key)
+            .collect();
+        assert_eq!(
+            vec![tests::node_key("abc123"), tests::node_key("ghi789")],
+            public_keys
+        );
+    }
+
+    #[test]
+    fn page_contains_at_most_limit_nodes_starting_at_offset() {
+        let service = service_with_measured_nodes();
+        let page = service.reputations_page(None, 1, 1);
+        assert_eq!(3, page.total);
+        assert_eq!(1, page.items.len());
+        assert_eq!(tests::node_key("def456"), page.items[0].public_key);
+    }
+
+    #[test]
+    fn page_past_the_end_is_empty_but_still_has_total() {
+        let service = service_with_measured_nodes();
+        let page = service.reputations_page(Some(1), 5, 10);
+        assert_eq!(2, page.total);
+        assert!(page.items.is_empty());
+    }
+
+    #[test]
+    fn with_unknown_layer_nothing_is_returned() {
+        let service = service_with_measured_nodes();
+        assert!(service.reputations(Some(3)).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod underperforming {
+    use super::*;
+
+    fn record_sweeps(service: &mut Service, name: &str, packets_received: &[u64]) {
+        // reports have to be newer than the ones recorded before
+        let first_sweep = service
+            .last_measurement_recorded()
+            .map_or(0, |last| last + 1);
+        for (sweep, &received) in packets_received.iter().enumerate() {
+            service
+                .apply_report(
+                    tests::MONITOR_KEY,
+                    Report {
+                        timestamp: first_sweep + sweep as u64,
+                        results: vec![tests::fake_node_test_result(name, 1, received)],
+                    },
+                )
+                .unwrap();
+        }
+    }
+
+    fn underperforming_keys(service: &Service) -> Vec<String> {
+        service
+            .underperforming()
+            .into_iter()
+            .map(|reputation| reputation.public_key)
+            .collect()
+    }
+
+    #[test]
+    fn node_bad_for_the_last_sweeps_is_underperforming() {
+        let mut service = Service::new(MixminingDb::new());
+        record_sweeps(&mut service, "abc123", &[10, 2, 1, 0]);
+        assert_eq!(
+            vec![tests::node_key("abc123")],
+            underperforming_keys(&service)
+        );
+    }
+
+    #[test]
+    fn node_with_a_single_bad_sweep_is_not_underperforming() {
+        let mut service = Service::new(MixminingDb::new());
+        record_sweeps(&mut service, "abc123", &[10, 10, 10, 0]);
+        assert!(underperforming_keys(&service).is_empty());
+    }
+
+    #[test]
+    fn node_that_recovered_is_not_underperforming() {
+        let mut service = Service::new(MixminingDb::new());
+        record_sweeps(&mut service, "abc123", &[0, 0, 0, 10]);
+        assert!(underperforming_keys(&service).is_empty());
+    }
+
+    #[test]
+    fn node_measured_fewer_times_than_required_is_not_underperforming() {
+        let mut service = Service::new(MixminingDb::new());
+        record_sweeps(&mut service, "abc123", &[0, 0]);
+        assert!(underperforming_keys(&service).is_empty());
+    }
+
+    #[test]
+    fn criteria_can_be_configured() {
+        let mut service = Service::new(MixminingDb::new()).with_underperformance_criteria(0.95, 1);
+        record_sweeps(&mut service, "abc123", &[9]);
+        record_sweeps(&mut service, "def456", &[10]);
+        assert_eq!(
+            vec![tests::node_key("abc123")],
+            underperforming_keys(&service)
+        );
+    }
+}
+
+#[cfg(test)]
+mod history {
+    use super::*;
+
+    #[test]
+    fn contains_measurements_from_each_report() {
+        let mut service = Service::new(MixminingDb::new());
+        for timestamp in &[100, 200] {
+            service
+                .apply_report(
+                    tests::MONITOR_KEY,
+                    Report {
+                        timestamp: *timestamp,
+                        results: vec![tests::fake_node_test_result("abc123", 1, 7)],
+                    },
+                )
+                .unwrap();
+        }
+
+        let timestamps: Vec<_> = service
+            .history(&tests::node_key("abc123"))
+            .unwrap()
+            .into_iter()
+            .map(|measurement| measurement.timestamp)
+            .collect();
+        assert_eq!(vec![100, 200], timestamps);
+    }
+
+    #[test]
+    fn is_none_for_unknown_mixnode() {
+        let service = Service::new(MixminingDb::new());
+        assert!(service.history(&tests::node_key("abc123")).is_none());
+    }
+}
+
+#[cfg(test)]
+mod layer_balance {
+    use super::*;
+
+    // a while after the measurements taken by the tests
+    const NOW: u64 = 6000;
+
+    fn service_with_measured_nodes() -> Service {
+        let mut service =
+            Service::new(MixminingDb::new()).with_layer_balance_criteria(3, 2, 10_000);
+        service
+            .apply_report(
+                tests::MONITOR_KEY,
+                Report {
+                    timestamp: 1234,
+                    results: vec![
+                        tests::fake_node_test_result("abc123", 1, 10),
+                        tests::fake_node_test_result("def456", 1, 4),
+                        tests::fake_node_test_result("ghi789", 2, 0),
+                    ],
+                },
+            )
+            .unwrap();
+        service
+    }
+
+    #[test]
+    fn layers_are_summarised_from_latest_measurements() {
+        let balance = service_with_measured_nodes().layer_balance_at(NOW);
+
+        assert_eq!(
+            LayerBalance {
+                layer: 1,
+                mixnodes: 2,
+                healthy_mixnodes: 2,
+                reliability: Some(0.7),
+                underpopulated: false,
+            },
+            balance[0]
+        );
+        assert_eq!(
+            LayerBalance {
+                layer: 2,
+                mixnodes: 1,
+                healthy_mixnodes: 0,
+                reliability: Some(0.0),
+                underpopulated: true,
+            },
+            balance[1]
+        );
+    }
+
+    #[test]
+    fn layers_without_measured_nodes_are_underpopulated() {
+        let balance = service_with_measured_nodes().layer_balance_at(NOW);
+
+        assert_eq!(3, balance.len());
+        assert_eq!(3, balance[2].layer);
+        assert_eq!(0, balance[2].mixnodes);
+        assert_eq!(None, balance[2].reliability);
+        assert!(balance[2].underpopulated);
+    }
+
+    #[test]
+    fn unexpected_layers_are_reported_as_well() {
+        let mut service = service_with_measured_nodes();
+        service
+            .apply_report(
+                tests::MONITOR_KEY,
+                Report {
+                    timestamp: 5678,
+                    results: vec![tests::fake_node_test_result("jkl012", 5, 10)],
+                },
+            )
+            .unwrap();
+
+        let layers: Vec<_> = service
+            .layer_balance_at(NOW)
+            .into_iter()
+            .map(|balance| balance.layer)
+            .collect();
+        assert_eq!(vec![1, 2, 3, 5], layers);
+    }
+
+    #[test]
+    fn nodes_no_longer_measured_are_not_counted() {
+        let mut service = Service::new(MixminingDb::new()).with_layer_balance_criteria(3, 1, 1000);
+        for (timestamp, name) in &[(1000, "abc123"), (1500, "def456"), (2500, "ghi789")] {
+            service
+                .apply_report(
+                    tests::MONITOR_KEY,
+                    Report {
+                        timestamp: *timestamp,
+                        results: vec![tests::fake_node_test_result(name, 1, 10)],
+                    },
+                )
+                .unwrap();
+        }
+
+        // abc123 was last measured more than a second ago
+        let balance = service.layer_balance_at(2500);
+        assert_eq!(2, balance[0].mixnodes);
+        assert_eq!(2, balance[0].healthy_mixnodes);
+    }
+
+    #[test]
+    fn layers_with_only_departed_nodes_are_underpopulated() {
+        let mut service = Service::new(MixminingDb::new()).with_layer_balance_criteria(2, 1, 1000);
+        for (timestamp, name, layer) in &[(1000, "abc123", 1), (5000, "def456", 2)] {
+            service
+                .apply_report(
+                    tests::MONITOR_KEY,
+                    Report {
+                        timestamp: *timestamp,
+                        results: vec![tests::fake_node_test_result(name, *layer, 10)],
+                    },
+                )
+                .unwrap();
+        }
+
+        let balance = service.layer_balance_at(5000);
+        assert_eq!(0, balance[0].mixnodes);
+        assert_eq!(None, balance[0].reliability);
+        assert!(balance[0].underpopulated);
+        assert!(!balance[1].underpopulated);
+    }
+
+    #[test]
+    fn nodes_are_not_counted_once_measurements_stop_coming_in() {
+        let mut service = Service::new(MixminingDb::new()).with_layer_balance_criteria(1, 1, 1000);
+        service
+            .apply_report(
+                tests::MONITOR_KEY,
+                Report {
+                    timestamp: 1000,
+                    results: vec![tests::fake_node_test_result("abc123", 1, 10)],
+                },
+            )
+            .unwrap();
+
+        assert_eq!(1, service.layer_balance_at(2000)[0].mixnodes);
+        // even though it's still the newest measurement there is
+        let balance = service.layer_balance_at(3000);
+        assert_eq!(0, balance[0].mixnodes);
+        assert!(balance[0].underpopulated);
+    }
+}
@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Length of an encoded mixnode identity key.
+const NODE_KEY_LENGTH: usize = 32;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Mixnode {
@@ -49,3 +54,168 @@ pub struct Validator {
     last_seen: u64,
     location: String,
 }
+
+/// A single reliability measurement of a mixnode, as reported by a network monitor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub timestamp: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+impl Measurement {
+    /// Fraction of the sent test packets that made it back to the monitor.
+    pub fn reliability(&self) -> f64 {
+        if self.packets_sent == 0 {
+            return 0.0;
+        }
+        self.packets_received as f64 / self.packets_sent as f64
+    }
+
+    /// A mixnode is considered healthy if at least one of the test packets made it through it.
+    pub fn is_healthy(&self) -> bool {
+        self.packets_received > 0
+    }
+}
+
+/// Reputation keeps track of how well a given mixnode has been performing
+/// according to the measurements submitted by network monitors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reputation {
+    pub public_key: String,
+    pub layer: u64,
+    pub latest: Measurement,
+    // the most recent measurements (including the latest one), oldest first
+    #[serde(default)]
+    pub recent: Vec<Measurement>,
+    // records written before this field existed simply have no known healthy measurement
+    #[serde(default)]
+    pub last_healthy: Option<u64>,
+    // exponential moving average of the reliability; missing in records written before it existed
+    #[serde(default)]
+    pub reliability_ema: Option<f64>,
+}
+
+impl Reputation {
+    /// Timestamp of the most recent measurement of the mixnode, healthy or not.
+    pub fn last_tested(&self) -> u64 {
+        self.latest.timestamp
+    }
+
+    /// Reliability of the mixnode smoothed over all of its measurements, with the recent ones
+    /// weighing the most. Falls back to the latest reliability if the average isn't known yet.
+    pub fn smoothed_reliability(&self) -> f64 {
+        self.reliability_ema
+            .unwrap_or_else(|| self.latest.reliability())
+    }
+
+    /// Whether each of the last `sweeps` measurements of the mixnode has been below the given
+    /// reliability threshold. Mixnodes that have been measured fewer times are never considered
+    /// to be underperforming, so that a single bad sweep of a new node doesn't get it flagged.
+    pub fn is_underperforming(&self, threshold: f64, sweeps: usize) -> bool {
+        if sweeps == 0 || self.recent.len() < sweeps {
+            return false;
+        }
+        self.recent[self.recent.len() - sweeps..]
+            .iter()
+            .all(|measurement| measurement.reliability() < threshold)
+    }
+}
+
+/// Result of testing a single mixnode during a network monitor run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeTestResult {
+    pub public_key: String,
+    pub layer: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+impl NodeTestResult {
+    /// Makes sure the result is one a monitor could have actually observed. It's checked
+    /// no matter how the result was submitted, so that nothing else ends up in the reputations.
+    pub fn validate(&self) -> Result<(), InvalidMeasurement> {
+        let key_length = bs58::decode(&self.public_key)
+            .into_vec()
+            .map_err(|_| InvalidMeasurement::NodeKey("not valid base58".into()))?
+            .len();
+        if key_length != NODE_KEY_LENGTH {
+            return Err(InvalidMeasurement::NodeKey(format!(
+                "expected {} bytes, got {}",
+                NODE_KEY_LENGTH, key_length
+            )));
+        }
+        if self.packets_sent == 0 {
+            return Err(InvalidMeasurement::Packets("no packets were sent".into()));
+        }
+        // this is what keeps the reliability within [0, 1]
+        if self.packets_received > self.packets_sent {
+            return Err(InvalidMeasurement::Packets(format!(
+                "received {} packets out of {} sent",
+                self.packets_received, self.packets_sent
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Why the result of testing a mixnode was rejected.
+#[derive(Debug, PartialEq)]
+pub enum InvalidMeasurement {
+    NodeKey(String),
+    Packets(String),
+}
+
+impl fmt::Display for InvalidMeasurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidMeasurement::NodeKey(reason) => {
+                write!(f, "invalid node public key - {}", reason)
+            }
+            InvalidMeasurement::Packets(reason) => write!(f, "invalid measurement - {}", reason),
+        }
+    }
+}
+
+/// Report contains the results of a single network monitor run over the whole network.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub timestamp: u64,
+    pub results: Vec<NodeTestResult>,
+}
+
+/// Bookkeeping of the Tendermint ABCI application, which is persisted along with
+/// the measurements of each committed block, starting from the chain's genesis.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainState {
+    // height of the last committed block
+    #[serde(default)]
+    pub height: i64,
+    // app hash resulting from the last committed block
+    #[serde(default)]
+    pub app_hash: Vec<u8>,
+    // nonce of the last transaction committed from each monitor, by its public key
+    #[serde(default)]
+    pub nonces: BTreeMap<String, u64>,
+    // base58-encoded public keys of the monitors allowed to submit transactions, as set in the genesis
+    #[serde(default)]
+    pub authorized_monitors: Vec<String>,
+}
+
+/// How well a single mixnet layer is populated, based on the latest measurements of its mixnodes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerBalance {
+    pub layer: u64,
+    pub mixnodes: usize,
+    pub healthy_mixnodes: usize,
+    // average reliability of the layer's mixnodes, unknown if there are none
+    pub reliability: Option<f64>,
+    pub underpopulated: bool,
+}
+
+/// A slice of a larger result set, along with the size of the whole set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page<T> {
+    pub total: usize,
+    pub items: Vec<T>,
+}
@@ -1,11 +1,46 @@
 #[cfg(test)]
-pub fn fake_mixnode(location: &str) -> super::Mixnode {
+pub const MONITOR_KEY: &str = "monitor123";
+
+#[cfg(test)]
+pub fn fake_mixnode(public_key: &str, location: &str) -> super::Mixnode {
     super::Mixnode {
         host: String::from("foo.com"),
         last_seen: 123,
         location: String::from(location),
-        public_key: String::from("abc123"),
+        public_key: String::from(public_key),
         stake: 8,
         version: String::from("1.0"),
     }
 }
+
+#[cfg(test)]
+pub fn measurement(timestamp: u64, packets_received: u64) -> super::Measurement {
+    super::Measurement {
+        timestamp,
+        packets_sent: 10,
+        packets_received,
+    }
+}
+
+// A valid mixnode public key standing in for the given name of at most 32 bytes. As long as
+// the names start with a lowercase letter, their keys are ordered just like the names themselves.
+#[cfg(test)]
+pub fn node_key(name: &str) -> String {
+    let mut key = [0u8; 32];
+    key[..name.len()].copy_from_slice(name.as_bytes());
+    bs58::encode(key).into_string()
+}
+
+#[cfg(test)]
+pub fn fake_node_test_result(
+    name: &str,
+    layer: u64,
+    packets_received: u64,
+) -> super::NodeTestResult {
+    super::NodeTestResult {
+        public_key: node_key(name),
+        layer,
+        packets_sent: 10,
+        packets_received,
+    }
+}
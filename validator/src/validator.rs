@@ -12,46 +12,188 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-// use crate::config::Config;
+use crate::config::Config;
 use crate::network::rest;
 use crate::network::tendermint;
 use crate::services::mixmining;
+use crypto::asymmetric::identity;
+use log::{error, info};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
 
 pub struct Validator {
     // when you re-introduce keys, check which ones you want:
     //    MixIdentityKeyPair (like 'nym-client' ) <- probably that one (after maybe renaming to just identity::KeyPair)
     //    encryption::KeyPair (like 'nym-mixnode' or 'sfw-provider')
-    tendermint_abci: tendermint::Abci,
+    // only running when the measurements are obtained through Tendermint
+    tendermint_abci: Option<tendermint::Abci>,
+    // set once the ABCI server is up, so that the REST API can report the validator as healthy
+    abci_ready: Arc<AtomicBool>,
     rest_api: rest::Api,
+    mixmining_service: Arc<Mutex<mixmining::Service>>,
+}
+
+fn monitor_public_key(encoded: &str) -> identity::PublicKey {
+    identity::PublicKey::from_base58_string(encoded).unwrap_or_else(|_| {
+        panic!(
+            "Invalid authorized monitor public key '{}' in the config",
+            encoded
+        )
+    })
 }
 
 impl Validator {
-    pub fn new() -> Self {
-        let mixmining_db = mixmining::db::MixminingDb::new();
-        let mixmining_service = mixmining::Service::new(mixmining_db);
+    pub fn new(config: Config) -> Self {
+        let mixmining_db = mixmining::db::MixminingDb::open(config.get_mix_mining_database_path())
+            .expect("Failed to open the mixmining database")
+            .with_history_depth(config.get_mix_mining_measurement_history_depth())
+            .with_reliability_smoothing(
+                config.get_mix_mining_reliability_smoothing_factor(),
+                config.get_mix_mining_reliability_smoothing_reset_after(),
+            );
+        let mixmining_service = mixmining::Service::new(mixmining_db)
+            .with_underperformance_criteria(
+                config.get_mix_mining_reliability_threshold(),
+                config.get_mix_mining_underperforming_sweeps(),
+            )
+            .with_layer_balance_criteria(
+                config.get_mix_mining_mix_layers(),
+                config.get_mix_mining_minimum_layer_size(),
+                config.get_mix_mining_layer_balance_max_age(),
+            );
+        let mixmining_service = Arc::new(Mutex::new(mixmining_service));
+
+        let authorized_monitors: Vec<_> = config
+            .get_mix_mining_authorized_monitors()
+            .iter()
+            .map(|key| monitor_public_key(key))
+            .collect();
 
-        let rest_api = rest::Api::new(mixmining_service);
+        let mut rest_api = rest::Api::new(
+            config.get_rest_api_listening_address(),
+            Arc::clone(&mixmining_service),
+        )
+        .with_report_rate_limit(config.get_rest_api_report_submissions_per_minute())
+        .with_authorized_monitors(authorized_monitors);
+        if config.get_rest_api_json_rpc_enabled() {
+            rest_api = rest_api.with_json_rpc();
+        }
+        if let Some(path) = config.get_rest_api_tls_identity_path() {
+            rest_api = rest_api.with_tls(rest::TlsIdentity {
+                path,
+                password: config.get_rest_api_tls_identity_password(),
+            });
+        }
+        let abci_ready = Arc::new(AtomicBool::new(false));
+        let tendermint_abci = if config.get_tendermint_enabled() {
+            // the mixmining state has to change only through the chain
+            rest_api = rest_api
+                .without_report_submissions()
+                .with_abci_readiness(Arc::clone(&abci_ready));
+            Some(tendermint::Abci::new(
+                config.get_abci_address(),
+                Arc::clone(&mixmining_service),
+            ))
+        } else {
+            None
+        };
 
         Validator {
             rest_api,
+            tendermint_abci,
+            abci_ready,
+            mixmining_service,
+        }
+    }
+
+    #[cfg(unix)]
+    async fn wait_for_interrupt() {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(err) => {
+                error!("There was an error while capturing SIGTERM - {:?}", err);
+                return Self::wait_for_ctrl_c().await;
+            }
+        };
+
+        tokio::select! {
+            _ = Self::wait_for_ctrl_c() => {},
+            _ = terminate.recv() => println!("Received SIGTERM"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn wait_for_interrupt() {
+        Self::wait_for_ctrl_c().await
+    }
 
-            // perhaps you might want to pass &config to the constructor
-            // there to get the config.tendermint (assuming you create appropriate fields + getters)
-            tendermint_abci: tendermint::Abci::new(),
+    async fn wait_for_ctrl_c() {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!(
+                "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
+                err
+            );
         }
+        println!("Received SIGINT");
     }
 
-    // TODO: Fix Tendermint startup here, see https://github.com/nymtech/nym/issues/147
     pub fn start(self) {
         let mut rt = Runtime::new().unwrap();
-        rt.spawn(self.rest_api.run());
-        rt.spawn(self.tendermint_abci.run());
+        let (rest_ready_sender, rest_ready) = oneshot::channel();
+        let (abci_ready_sender, abci_ready) = oneshot::channel();
+
+        // both the REST API and the ABCI server block the thread they're running on
+        let rest_api = self.rest_api;
+        let rest_handle = rt
+            .handle()
+            .spawn_blocking(move || rest_api.run(rest_ready_sender));
+        let abci_handle = self.tendermint_abci.map(|tendermint_abci| {
+            rt.handle()
+                .spawn_blocking(move || tendermint_abci.run(abci_ready_sender))
+        });
+        let abci_enabled = abci_handle.is_some();
+        let abci_ready_flag = self.abci_ready;
+
+        let started = rt.block_on(async {
+            if rest_ready.await.is_err() {
+                error!("REST API failed to start");
+                return false;
+            }
+            if abci_enabled {
+                if abci_ready.await.is_err() {
+                    error!("Tendermint ABCI failed to start");
+                    return false;
+                }
+                abci_ready_flag.store(true, Ordering::SeqCst);
+            }
+            true
+        });
+
+        if !started {
+            // the other task might be blocking its thread indefinitely, so we can't wait for it
+            std::process::exit(1);
+        }
 
-        // TODO: this message is going to come out of order (if at all), as spawns are async, see issue above
         println!("Validator startup complete.");
-        rt.block_on(blocker());
+        rt.block_on(Self::wait_for_interrupt());
+        println!("The validator will terminate now");
+
+        // hold on to the service until we're gone, so that nothing can get recorded after the flush
+        let mixmining_service = self.mixmining_service.lock().unwrap();
+        match mixmining_service.flush() {
+            Ok(_) => info!("Flushed the mixmining database"),
+            Err(err) => error!("Failed to flush the mixmining database - {}", err),
+        }
+
+        // neither the REST API nor the ABCI server can be stopped once running, so rather than
+        // waiting for their threads to finish, just stop the runtime and leave them behind
+        drop(rest_handle);
+        drop(abci_handle);
+        rt.shutdown_timeout(Duration::from_millis(100));
     }
 }
-
-pub async fn blocker() {} // once Tendermint unblocks us, make this block forever.